@@ -1,5 +1,11 @@
+use std::path::PathBuf;
 use clap::Parser;
 use clap::Subcommand;
+use crate::dates::Period;
+use crate::export::ExportFormat;
+use crate::import::ImportFormat;
+use crate::models::AbsenceKind;
+use crate::report::{ReportFormat, ReportGrouping};
 
 /// Does your paperwork
 #[derive(Parser, Debug)]
@@ -30,18 +36,87 @@ pub enum Commands {
         sub_command: EventCommand,
     },
     /// execute all probes and insert all detected events
-    Detect {},
+    Detect {
+        /// keep running and detect again after the configured interval
+        #[arg(short, long)]
+        watch: bool,
+    },
+    /// detects the workplace periodically in the background
+    Daemon {
+        #[command(subcommand)]
+        sub_command: DaemonCommand,
+    },
 
-    SQL {},
+    Sql {},
 
-    /// removes database
-    Clear {},
+    /// removes database after saving an automatic backup
+    Clear {
+        /// do not ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// creates and restores snapshots of the whole database
+    Backup {
+        #[command(subcommand)]
+        sub_command: BackupCommand,
+    },
+    /// maintenance of the database
+    Db {
+        #[command(subcommand)]
+        sub_command: DbCommand,
+    },
     /// show current configuration
     Config {},
     Toggl {
         #[command(subcommand)]
         sub_command: TogglCommand,
     },
+    /// tracks working time locally without toggl
+    Track {
+        #[command(subcommand)]
+        sub_command: TrackCommand,
+    },
+    /// sums up the time entries per project, client, tag or description
+    Report {
+        /// what the time entries are summed up by
+        #[arg(short, long, value_enum, default_value_t = ReportGrouping::Project)]
+        by: ReportGrouping,
+        /// splits the report into weeks, months or years
+        #[arg(short, long, value_enum)]
+        period: Option<Period>,
+        #[arg(short, long, value_enum, default_value_t = ReportFormat::Table)]
+        format: ReportFormat,
+        /// begin of the report
+        start: Option<String>,
+        /// end of the report.
+        /// leave blank for today
+        end: Option<String>,
+    },
+    /// imports time entries from other trackers
+    Time {
+        #[command(subcommand)]
+        sub_command: TimeCommand,
+    },
+    /// prints the monthly working time record
+    Timesheet {
+        #[command(subcommand)]
+        sub_command: TimesheetCommand,
+    },
+    /// records vacation, sick leave and other absences
+    Absence {
+        #[command(subcommand)]
+        sub_command: AbsenceCommand,
+    },
+    /// overrides the expected working time of single days
+    Expected {
+        #[command(subcommand)]
+        sub_command: ExpectedCommand,
+    },
+    /// corrects the saldo, eg. for paid out overtime
+    Saldo {
+        #[command(subcommand)]
+        sub_command: SaldoCommand,
+    },
 }
 
 #[derive(Debug, Subcommand)]
@@ -60,6 +135,11 @@ pub enum ProbeCommand {
     },
     /// shows all configured probes
     Show {},
+    /// runs a single probe and explains why it was detected or not
+    Test {
+        /// name of the probe.
+        event: Event,
+    },
 }
 
 #[derive(Debug, Subcommand)]
@@ -72,6 +152,14 @@ pub enum TogglCommand {
         #[arg(short, long)]
         compact: bool,
 
+        /// show the time entries from the database without contacting toggl
+        #[arg(long)]
+        offline: bool,
+
+        /// sums up the timesheet per week, month or year
+        #[arg(short, long, value_enum)]
+        group_by: Option<Period>,
+
         /// begin of the timesheet
         start: Option<String>,
         /// end of the timesheet.
@@ -79,20 +167,56 @@ pub enum TogglCommand {
         end: Option<String>,
     },
 
-    Export {},
+    Export {
+        /// sums up the timesheet per week, month or year
+        #[arg(short, long, value_enum)]
+        group_by: Option<Period>,
+
+        #[command(flatten)]
+        options: ExportOptions,
+    },
+
+    /// lists all time entries with their workspace, client, project and tags
+    Entries {
+        /// begin of the list
+        start: Option<String>,
+        /// end of the list.
+        /// leave blank for today
+        end: Option<String>,
+    },
+
+    /// downloads the time entries changed since the last sync
+    Sync {
+        /// download all time entries from this date on, eg. to fetch older history
+        #[arg(long)]
+        from: Option<String>,
+        /// end of the download.
+        /// leave blank for today
+        #[arg(long, requires = "from")]
+        to: Option<String>,
+    },
 }
 
 #[derive(Debug, Subcommand)]
 pub enum EventCommand {
-    /// export database to json
-    Export {},
+    /// export the presence intervals, including compacted detections, to json, csv, tsv or xlsx
+    Export {
+        #[command(flatten)]
+        options: ExportOptions,
+    },
 
-    /// import database from json
+    /// import presence intervals of an export or single events from json
     Import {},
 
-    /// list all events as a table
+    /// list the detected events not yet compacted as a table
     List {},
 
+    /// shows the stays at every location per day, eg. office 08:12–16:40, home 17:30–19:00
+    Timeline {
+        /// a date like 2023-04-03 or a range like 2023-04-03..2023-04-07, leave blank for today
+        range: Option<String>,
+    },
+
     /// inserts a new event to the database
     Insert {
         /// manually overwrite the timestamp of the entry
@@ -103,3 +227,186 @@ pub enum EventCommand {
     },
 
 }
+
+#[derive(Debug, Subcommand)]
+pub enum AbsenceCommand {
+    /// records an absence for every workday between start and end
+    Add {
+        /// kind of the absence
+        kind: AbsenceKind,
+        /// first day of the absence
+        start: String,
+        /// last day of the absence.
+        /// leave blank for a single day
+        end: Option<String>,
+        /// only absent for half of the day
+        #[arg(long)]
+        half: bool,
+    },
+    /// lists all absences of a year and the remaining vacation days
+    List {
+        /// leave blank for the current year
+        year: Option<i32>,
+    },
+    /// removes all absences between start and end
+    Remove {
+        /// first day to remove
+        start: String,
+        /// last day to remove.
+        /// leave blank for a single day
+        end: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ExpectedCommand {
+    /// overrides the expected working time.
+    /// For a range only the days the schedule expects work are changed
+    Set {
+        /// a date like 2023-04-03 or a range like 2023-04-03..2023-04-07
+        range: String,
+        /// like 4h30m, 90m, 4:30 or 4.5
+        duration: String,
+    },
+    /// lists the overridden expected working times
+    List {
+        /// a date or range, leave blank for the current year
+        range: Option<String>,
+        /// also list the expected working times from the schedule
+        #[arg(short, long)]
+        all: bool,
+    },
+    /// removes the overrides, so the schedule applies again
+    Reset {
+        /// a date like 2023-04-03 or a range like 2023-04-03..2023-04-07
+        range: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SaldoCommand {
+    /// adds an amount to the saldo on a date
+    Adjust {
+        /// date like 2023-04-03
+        date: String,
+        /// like 10h or -10h30m to reduce the saldo
+        #[arg(allow_hyphen_values = true)]
+        amount: String,
+        /// why the saldo changed, eg. "overtime paid out"
+        reason: String,
+    },
+    /// lists the adjustments of a year and the saldo at its end
+    List {
+        /// leave blank for the current year
+        year: Option<i32>,
+    },
+    /// removes a manual adjustment
+    Remove {
+        /// id of the adjustment as shown by list
+        id: i64,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DaemonCommand {
+    /// runs all probes periodically until stopped by SIGTERM, same as detect --watch
+    Run {},
+    /// writes a systemd user unit for the daemon
+    InstallUnit {},
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TrackCommand {
+    /// starts a new time entry now
+    Start {
+        /// what you are working on
+        description: Option<String>,
+    },
+    /// stops the running time entry now
+    Stop {},
+    /// shows the running time entry
+    Status {},
+    /// adds a finished time entry
+    Add {
+        /// begin of the time entry
+        from: String,
+        /// end of the time entry
+        to: String,
+        /// what you have been working on
+        description: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TimesheetCommand {
+    /// renders the working time record of a month to html and pdf
+    ///
+    /// The pdf is converted from the html by an external program: the pdf_command in the [print] section
+    /// of the configuration, eg. pdf_command = "wkhtmltopdf --quiet {input} {output}", or else wkhtmltopdf
+    /// or chromium if they are in the PATH. It is an error if there is none, unless --no-pdf is given.
+    Print {
+        /// month to print like 2023-04, defaults to the current month
+        #[arg(short, long)]
+        month: Option<String>,
+
+        /// html file to write, defaults to timesheet-<month>.html.
+        /// the pdf is written next to it
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// only write the html
+        #[arg(long)]
+        no_pdf: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TimeCommand {
+    /// imports time entries from a csv file.
+    /// Importing the same file again updates the time entries instead of duplicating them
+    Import {
+        path: PathBuf,
+
+        #[arg(short, long, value_enum, default_value_t = ImportFormat::Csv)]
+        format: ImportFormat,
+
+        /// import time entries even if they overlap other time entries
+        #[arg(long)]
+        allow_overlaps: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DbCommand {
+    /// compacts old detections into intervals and shrinks the database file
+    Vacuum {},
+}
+
+#[derive(Debug, Subcommand)]
+pub enum BackupCommand {
+    /// saves a snapshot of the database with all tables and the schema version
+    Create {
+        /// file to write the snapshot to, defaults to papierkram-<timestamp>.db
+        path: Option<PathBuf>,
+    },
+    /// replaces the database by a snapshot after saving an automatic backup
+    Restore {
+        path: PathBuf,
+
+        /// do not ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// lists the automatic backups
+    List {},
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ExportOptions {
+    #[arg(short, long, value_enum, default_value_t = ExportFormat::Json)]
+    pub format: ExportFormat,
+
+    /// file to write the export to instead of stdout
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
@@ -0,0 +1,148 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use log::debug;
+use rusqlite::Connection;
+
+use crate::datastore::DataStore;
+
+/// prefix of the files of automatic backups, everything else in the backup directory is left alone
+static AUTOMATIC_BACKUP_PREFIX: &str = "papierkram-auto-";
+
+#[derive(thiserror::Error, Debug)]
+pub enum BackupError {
+    #[error("The backup {0} already exists")]
+    AlreadyExists(String),
+
+    #[error("The backup {0} does not exist")]
+    NotFound(String),
+
+    #[error("The backup {0} is corrupted")]
+    Corrupted(String),
+
+    #[error("Could not access the backup directory {0}")]
+    Io(String, #[source] io::Error),
+}
+
+/// Writes a snapshot of the database to the given path
+pub fn create_backup(connection: &mut Connection, path: &Path) -> anyhow::Result<()> {
+    if path.exists() {
+        return Err(BackupError::AlreadyExists(path.display().to_string()).into());
+    }
+    connection.backup_into(path)
+}
+
+/// Copies the database file into the backup directory
+/// and removes the oldest automatic backups so that only keep of them are left.
+/// The file is copied as it is, so databases which can not be opened anymore are backed up as well
+pub fn create_automatic_backup(database_path: &Path, backup_dir: &Path, reason: &str, keep: usize) -> anyhow::Result<PathBuf> {
+    fs::create_dir_all(backup_dir)
+        .map_err(|error| BackupError::Io(backup_dir.display().to_string(), error))?;
+
+    let name = format!("{AUTOMATIC_BACKUP_PREFIX}{}-{reason}.db", Local::now().format("%Y%m%d-%H%M%S"));
+    let path = backup_dir.join(name);
+    if path.exists() {
+        return Err(BackupError::AlreadyExists(path.display().to_string()).into());
+    }
+    fs::copy(database_path, &path)
+        .map_err(|error| BackupError::Io(path.display().to_string(), error))?;
+    debug!("Created automatic backup {}", path.display());
+
+    rotate_backups(backup_dir, keep)?;
+    Ok(path)
+}
+
+/// lists the automatic backups from the oldest to the newest
+pub fn list_automatic_backups(backup_dir: &Path) -> Result<Vec<PathBuf>, BackupError> {
+    if !backup_dir.exists() {
+        return Ok(vec![]);
+    }
+    let entries = fs::read_dir(backup_dir)
+        .map_err(|error| BackupError::Io(backup_dir.display().to_string(), error))?;
+
+    // the timestamp in the name sorts the backups by age
+    let mut backups: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(|name| name.starts_with(AUTOMATIC_BACKUP_PREFIX) && name.ends_with(".db"))
+            .unwrap_or(false))
+        .collect();
+    backups.sort();
+    Ok(backups)
+}
+
+fn rotate_backups(backup_dir: &Path, keep: usize) -> Result<(), BackupError> {
+    let backups = list_automatic_backups(backup_dir)?;
+    let outdated = backups.len().saturating_sub(keep);
+    for path in backups.iter().take(outdated) {
+        debug!("Removing old backup {}", path.display());
+        fs::remove_file(path)
+            .map_err(|error| BackupError::Io(path.display().to_string(), error))?;
+    }
+    Ok(())
+}
+
+/// Copies the backup next to the database and migrates it to the current schema.
+/// Returns the path of the prepared copy, which can replace the database file.
+/// Backups from newer versions of papierkram are refused.
+pub fn prepare_restore(backup: &Path, database_path: &Path) -> anyhow::Result<PathBuf> {
+    if !backup.exists() {
+        return Err(BackupError::NotFound(backup.display().to_string()).into());
+    }
+    let prepared = database_path.with_extension("restore");
+    fs::copy(backup, &prepared)?;
+
+    let mut connection = Connection::open(&prepared)?;
+    let checked = connection
+        .check_integrity()
+        .and_then(|ok| match ok {
+            true => { connection.run_migrations() }
+            false => { Err(BackupError::Corrupted(backup.display().to_string()).into()) }
+        });
+    drop(connection);
+
+    if let Err(error) = checked {
+        fs::remove_file(&prepared)?;
+        return Err(error);
+    }
+    Ok(prepared)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::backup::{create_automatic_backup, list_automatic_backups, rotate_backups, AUTOMATIC_BACKUP_PREFIX};
+
+    #[test]
+    fn test_rotate_backups() {
+        let backup_dir = std::env::temp_dir().join(format!("papierkram-test-backups-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&backup_dir);
+        fs::create_dir_all(&backup_dir).unwrap();
+        for day in 1..=4 {
+            fs::write(backup_dir.join(format!("{AUTOMATIC_BACKUP_PREFIX}2023030{day}-120000-clear.db")), "").unwrap();
+        }
+        fs::write(backup_dir.join("manual.db"), "").unwrap();
+
+        rotate_backups(&backup_dir, 2).unwrap();
+        let backups = list_automatic_backups(&backup_dir).unwrap();
+        assert_eq!(2, backups.len());
+        assert!(backups[0].ends_with(format!("{AUTOMATIC_BACKUP_PREFIX}20230303-120000-clear.db")));
+        assert!(backup_dir.join("manual.db").exists());
+
+        // a database which can not be opened is backed up as well
+        let database = backup_dir.join("papierkram.db");
+        fs::write(&database, "no database").unwrap();
+        let path = create_automatic_backup(&database, &backup_dir, "restore", 2).unwrap();
+        let backups = list_automatic_backups(&backup_dir).unwrap();
+        assert_eq!(2, backups.len());
+        assert_eq!(&path, backups.last().unwrap());
+        assert_eq!("no database", fs::read_to_string(&path).unwrap());
+
+        fs::remove_dir_all(&backup_dir).unwrap();
+    }
+}
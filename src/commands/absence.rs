@@ -0,0 +1,108 @@
+use chrono::{Datelike, Local, NaiveDate};
+use cli_table::{Cell, print_stdout, Style, Table};
+use rusqlite::Connection;
+use crate::args::AbsenceCommand;
+use crate::config::ApplicationConfig;
+use crate::datastore::DataStore;
+use crate::dates::parse_date;
+use crate::ErrorHandler;
+use crate::holidays::holidays_between;
+use crate::models::{Absence, AbsenceKind};
+use crate::table_cli_helper::TableFormatter;
+
+pub fn main(config: &ApplicationConfig, command: &AbsenceCommand, connection: &mut Connection) {
+    match command {
+        AbsenceCommand::Add { kind, start, end, half } => {
+            execute_add(config, connection, *kind, start, end, *half).handle_error();
+        }
+        AbsenceCommand::List { year } => {
+            let year = year.unwrap_or(Local::now().year());
+            execute_list(config, connection, year).handle_error();
+        }
+        AbsenceCommand::Remove { start, end } => {
+            execute_remove(connection, start, end).handle_error();
+        }
+    }
+}
+
+/// parses a date range where a missing end means a single day
+fn parse_date_range(start: &str, end: &Option<String>) -> anyhow::Result<(NaiveDate, NaiveDate)> {
+    let start = parse_date(start)?;
+    let end = match end {
+        None => { start }
+        Some(end) => { parse_date(end)? }
+    };
+    Ok((start.min(end), start.max(end)))
+}
+
+pub fn execute_add(
+    config: &ApplicationConfig,
+    connection: &mut Connection,
+    kind: AbsenceKind,
+    start: &str,
+    end: &Option<String>,
+    half: bool) -> anyhow::Result<()>
+{
+    let (start, end) = parse_date_range(start, end)?;
+    let holidays = match &config.holidays {
+        None => { vec![] }
+        Some(holidays) => { holidays_between(holidays, start, end)? }
+    };
+
+    // days off and holidays do not need to be taken as absence
+    let absences: Vec<Absence> = start
+        .iter_days()
+        .take_while(|date| *date <= end)
+        .filter(|date| config.workweek.expected_duration(*date).num_seconds() > 0)
+        .filter(|date| !holidays.iter().any(|holiday| holiday.date == *date))
+        .map(|date| Absence {
+            date,
+            kind,
+            fraction: if half { 0.5 } else { 1.0 },
+        })
+        .collect();
+
+    connection.insert_absences(&absences)?;
+    println!("Recorded {} days of {kind}.", absences.len());
+    Ok(())
+}
+
+pub fn execute_list(config: &ApplicationConfig, connection: &mut Connection, year: i32) -> anyhow::Result<()> {
+    let start = NaiveDate::from_ymd_opt(year, 1, 1).unwrap();
+    let end = NaiveDate::from_ymd_opt(year, 12, 31).unwrap();
+    let absences = connection.list_absences(start, end)?;
+
+    let table = absences
+        .iter()
+        .map(|x| x.into())
+        .collect::<Vec<Vec<cli_table::CellStruct>>>()
+        .table()
+        .title(vec![
+            "Date".cell().bold(true),
+            "Kind".cell().bold(true),
+            "Duration".cell().bold(true),
+        ])
+        .format_table();
+    print_stdout(table)?;
+
+    if let Some(vacation) = &config.vacation {
+        let taken: f64 = absences
+            .iter()
+            .filter(|absence| absence.kind == AbsenceKind::Vacation)
+            .map(|absence| absence.fraction)
+            .sum();
+        println!(
+            "Vacation {year}: {taken} of {} days taken, {} days remaining.",
+            vacation.days_per_year,
+            vacation.days_per_year - taken
+        );
+    }
+    Ok(())
+}
+
+pub fn execute_remove(connection: &mut Connection, start: &str, end: &Option<String>) -> anyhow::Result<()> {
+    let (start, end) = parse_date_range(start, end)?;
+    let removed = connection.remove_absences(start, end)?;
+    println!("Removed {removed} absences.");
+    Ok(())
+}
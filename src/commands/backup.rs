@@ -0,0 +1,123 @@
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use log::warn;
+use rusqlite::Connection;
+
+use crate::args::BackupCommand;
+use crate::backup::{create_automatic_backup, create_backup, list_automatic_backups, prepare_restore};
+use crate::config::ApplicationConfig;
+use crate::daemon::running_daemon;
+use crate::datastore::DataStore;
+use crate::ErrorHandler;
+
+pub fn main(config: &ApplicationConfig, command: &BackupCommand) {
+    match command {
+        BackupCommand::Create { path } => {
+            execute_create(config, path).handle_error();
+        }
+        BackupCommand::Restore { path, yes } => {
+            execute_restore(config, path, *yes).handle_error();
+        }
+        BackupCommand::List {} => {
+            execute_list(config).handle_error();
+        }
+    }
+}
+
+/// asks the user on stdin, everything but y and yes counts as no
+pub fn confirm(question: &str) -> anyhow::Result<bool> {
+    print!("{question} [y/N] ");
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+fn database_path(config: &ApplicationConfig) -> anyhow::Result<PathBuf> {
+    config.database_path().ok_or(anyhow::Error::msg("Could not determine the database path!"))
+}
+
+fn backup_dir(config: &ApplicationConfig) -> anyhow::Result<PathBuf> {
+    config.backup_dir().ok_or(anyhow::Error::msg("Could not determine the backup directory!"))
+}
+
+/// a running daemon keeps the database open and would write into the removed file
+fn refuse_while_daemon_runs() -> anyhow::Result<()> {
+    let pid_file_path = ApplicationConfig::pid_file_path().ok_or(anyhow::Error::msg("Could not determine the pid file path!"))?;
+    match running_daemon(&pid_file_path) {
+        None => { Ok(()) }
+        Some(pid) => {
+            Err(anyhow::Error::msg(format!(
+                "The papierkram daemon with pid {pid} is using the database. Stop it first, eg. with: systemctl --user stop papierkram"
+            )))
+        }
+    }
+}
+
+/// backs up the current database before it is replaced or removed
+fn backup_before(config: &ApplicationConfig, reason: &str) -> anyhow::Result<()> {
+    let database_path = database_path(config)?;
+    if !database_path.exists() {
+        return Ok(());
+    }
+    let path = create_automatic_backup(&database_path, &backup_dir(config)?, reason, config.backup.keep)?;
+    println!("Saved a backup of the database to {}", path.display());
+    Ok(())
+}
+
+pub fn execute_create(config: &ApplicationConfig, path: &Option<PathBuf>) -> anyhow::Result<()> {
+    let path = path
+        .clone()
+        .unwrap_or(PathBuf::from(format!("papierkram-{}.db", Local::now().format("%Y%m%d-%H%M%S"))));
+    let mut connection = Connection::connect_database(config)?;
+    create_backup(&mut connection, &path)?;
+    println!("Saved backup to {}", path.display());
+    Ok(())
+}
+
+pub fn execute_restore(config: &ApplicationConfig, path: &Path, yes: bool) -> anyhow::Result<()> {
+    refuse_while_daemon_runs()?;
+    let database_path = database_path(config)?;
+    let prepared = prepare_restore(path, &database_path)?;
+
+    if !yes && !confirm(&format!("Replace the database with the backup {}?", path.display()))? {
+        fs::remove_file(&prepared)?;
+        println!("Aborted.");
+        return Ok(());
+    }
+
+    backup_before(config, "restore")?;
+    fs::rename(&prepared, &database_path)?;
+    println!("Restored the database from {}", path.display());
+    Ok(())
+}
+
+pub fn execute_list(config: &ApplicationConfig) -> anyhow::Result<()> {
+    for path in list_automatic_backups(&backup_dir(config)?)? {
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+/// removes the database after a confirmation and an automatic backup
+pub fn execute_clear(config: &ApplicationConfig, yes: bool) -> anyhow::Result<()> {
+    refuse_while_daemon_runs()?;
+    let database_path = database_path(config)?;
+    if !database_path.exists() {
+        println!("There is no database to remove.");
+        return Ok(());
+    }
+    if !yes && !confirm(&format!("Remove the database {}?", database_path.display()))? {
+        println!("Aborted.");
+        return Ok(());
+    }
+
+    backup_before(config, "clear")?;
+    warn!("Removing old database and creating new.");
+    fs::remove_file(database_path)?;
+    Ok(())
+}
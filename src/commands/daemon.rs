@@ -0,0 +1,34 @@
+use std::fs;
+
+use anyhow::{anyhow, Context};
+use rusqlite::Connection;
+use crate::args::DaemonCommand;
+use crate::config::ApplicationConfig;
+use crate::daemon::systemd_unit;
+use crate::ErrorHandler;
+
+pub fn main(config: ApplicationConfig, command: &DaemonCommand, mut connection: Connection) {
+    match command {
+        DaemonCommand::Run {} => {
+            crate::commands::detect::execute_watch(&config, &mut connection).handle_error();
+        }
+        DaemonCommand::InstallUnit {} => {
+            execute_install_unit().handle_error();
+        }
+    }
+}
+
+/// writes a systemd user unit which runs the daemon with this executable
+pub fn execute_install_unit() -> anyhow::Result<()> {
+    let path = ApplicationConfig::systemd_unit_path().ok_or(anyhow!("Could not determine the systemd user directory"))?;
+    let executable = std::env::current_exe()?;
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    fs::write(&path, systemd_unit(&executable))
+        .with_context(|| format!("Could not write {}", path.display()))?;
+
+    println!("Wrote {}", path.display());
+    println!("Enable it with: systemctl --user daemon-reload && systemctl --user enable --now {}", env!("CARGO_PKG_NAME"));
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use std::fs;
+
+use anyhow::anyhow;
+use chrono::{Duration, Utc};
+use log::debug;
+use rusqlite::Connection;
+
+use crate::args::DbCommand;
+use crate::config::ApplicationConfig;
+use crate::datastore::DataStore;
+use crate::ErrorHandler;
+
+pub fn main(config: &ApplicationConfig, command: &DbCommand, connection: &mut Connection) {
+    match command {
+        DbCommand::Vacuum {} => {
+            execute_vacuum(config, connection).handle_error();
+        }
+    }
+}
+
+/// compacts the raw detections older than the retention into intervals.
+/// Returns the number of removed detections and of written intervals
+pub fn compact(config: &ApplicationConfig, connection: &mut Connection) -> anyhow::Result<(usize, usize)> {
+    // the intervals are built with the configured gap and are kept like this
+    connection.update_max_gap(Duration::seconds(config.timeline.max_gap_seconds as i64))?;
+    let before = Utc::now() - Duration::days(config.retention.raw_detection_days as i64);
+    let (detections, intervals) = connection.compact_detections(before)?;
+    debug!("Compacted {detections} detections before {before} into {intervals} intervals.");
+    Ok((detections, intervals))
+}
+
+pub fn execute_vacuum(config: &ApplicationConfig, connection: &mut Connection) -> anyhow::Result<()> {
+    let database_path = config.database_path().ok_or(anyhow!("Could not determine the database path!"))?;
+    let size_before = fs::metadata(&database_path)?.len();
+
+    let (detections, intervals) = compact(config, connection)?;
+    connection.vacuum()?;
+    let size_after = fs::metadata(&database_path)?.len();
+
+    println!("Compacted {detections} detections into {intervals} intervals.");
+    println!(
+        "The database shrank from {} to {}, {} saved.",
+        format_size(size_before),
+        format_size(size_after),
+        format_size(size_before.saturating_sub(size_after)),
+    );
+    Ok(())
+}
+
+/// formats a number of bytes with binary prefixes like 1.5 MiB
+fn format_size(bytes: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", units[unit])
+    }
+}
@@ -1,35 +1,86 @@
-use log::debug;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use chrono::Utc;
+use log::{debug, error, info, warn};
 use rusqlite::Connection;
+use crate::commands::db::compact;
 use crate::config::ApplicationConfig;
+use crate::daemon::{PidFile, sleep_unless_stopped, stop_flag};
 use crate::datastore::DataStore;
+use crate::models::Event;
+use crate::ErrorHandler;
+use crate::probe::run_probes;
+use crate::rules::{classify, compile};
 
 
-pub fn main(config: ApplicationConfig, mut connection: Connection) {
-    use std::process::Command;
-    let mut results = Vec::new();
-
+pub fn main(config: ApplicationConfig, mut connection: Connection, watch: bool) {
+    if watch {
+        execute_watch(&config, &mut connection).handle_error();
+    } else {
+        execute_detect(&config, &mut connection).handle_error();
+    }
+}
 
-    for (name, probe) in config.probes {
-        debug!("Running {name}: {}", probe.command);
+/// runs all probes once and saves the detected events.
+/// With rules only the first matching rule is saved, without every detected probe.
+pub fn execute_detect(config: &ApplicationConfig, connection: &mut Connection) -> anyhow::Result<()> {
+    let rules = compile(&config.rules, &config.probes)?;
+    let outcomes = run_probes(&config.probes);
+    let detected: Vec<&str> = outcomes
+        .iter()
+        .filter(|outcome| outcome.detected())
+        .map(|outcome| outcome.name.as_str())
+        .collect();
 
+    let events: Vec<&str> = if rules.is_empty() {
+        detected
+    } else {
+        match classify(&rules, &detected.iter().copied().collect()) {
+            None => {
+                println!("No rule matched the detected probes [{}]", detected.join(", "));
+                vec![]
+            }
+            Some(rule) => {
+                debug!("Rule {} matched: {}", rule.name, rule.condition);
+                vec![rule.name.as_str()]
+            }
+        }
+    };
 
-        let result = Command::new("sh")
-            .arg("-c")
-            .arg(probe.command)
-            .output()
-            .expect("failed to execute process")
-            .status
-            .success();
+    // all events of a run share the instant, so the presence intervals see them as detected together
+    let now = Utc::now();
+    for event in events {
+        connection
+            .insert_event(&Event { time: now, name: event.to_string() })
+            .context("There was an error while saving the result!")?;
+        println!("Detected {event}");
+    }
 
-        if result {
-            results.push(connection.insert_current_event(&name));
-            println!("Detected {}", name);
-        } else {
-            debug!("{name} was not detected.");
-        }
+    // keeps the table of detections small when detect runs every few minutes
+    if let Err(e) = compact(config, connection) {
+        warn!("Could not compact old detections: {e:?}");
     }
+    Ok(())
+}
+
+/// runs all probes periodically until SIGTERM or SIGINT is received
+pub fn execute_watch(config: &ApplicationConfig, connection: &mut Connection) -> anyhow::Result<()> {
+    let pid_file_path = ApplicationConfig::pid_file_path().ok_or(anyhow!("Could not determine the pid file path"))?;
+    let _pid_file = PidFile::acquire(&pid_file_path)?;
+    let stop = stop_flag()?;
+    let interval = Duration::from_secs(config.daemon.interval_seconds);
+    info!("Detecting every {} seconds.", interval.as_secs());
 
-    for result in results {
-        result.expect("There was an error while saving the result!");
+    loop {
+        // a failing detection must not stop the daemon
+        if let Err(error) = execute_detect(config, connection) {
+            error!("{error:?}");
+        }
+        if !sleep_unless_stopped(interval, &stop) {
+            break;
+        }
     }
+    info!("Stopped detecting.");
+    Ok(())
 }
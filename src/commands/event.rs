@@ -1,16 +1,18 @@
 
 use std::io;
-use chrono::{Utc};
-use cli_table::Cell;
+use chrono::{Duration, Local, NaiveDate, Utc};
 use log::{debug, error, warn};
 use rusqlite::Connection;
+use serde::Deserialize;
+use crate::args::ExportOptions;
 use crate::config::ApplicationConfig;
 use crate::datastore::DataStore;
 
-use crate::dates::{parse_date_time};
-use crate::models::Event;
+use crate::dates::{parse_date_range, parse_date_time};
+use crate::export::export;
+use crate::models::{Event, PresenceInterval};
 
-pub fn execute_add(mut connection: Connection, date: &Option<String>, location: &String) {
+pub fn execute_add(mut connection: Connection, date: &Option<String>, location: &str) {
     match date {
         None => {
             connection.insert_current_event(location)
@@ -25,7 +27,7 @@ pub fn execute_add(mut connection: Connection, date: &Option<String>, location:
                 Ok(date) => {
                     let office_location = Event {
                         time: date.with_timezone(&Utc),
-                        name: location.clone(),
+                        name: location.to_string(),
                     };
                     connection.insert_event(&office_location)
                         .expect("Could not add location!")
@@ -55,25 +57,61 @@ pub fn execute_list(mut connection: Connection) {
 }
 
 
-pub fn execute_export(_config: ApplicationConfig, mut connection: Connection) {
-    let rows = connection.list_events().expect("Could not load rows from database!");
-    let json = serde_json::to_string_pretty(&rows).expect("Could not serialize to json!");
-    println!("{}", json);
+/// prints the stays at the detected locations of every day in the range
+pub fn execute_timeline(config: &ApplicationConfig, connection: &mut Connection, range: &Option<String>) -> anyhow::Result<()> {
+    let (start, end) = match range {
+        None => {
+            let today = Local::now().date_naive();
+            (today, today)
+        }
+        Some(range) => { parse_date_range(range)? }
+    };
+    connection.update_max_gap(Duration::seconds(config.timeline.max_gap_seconds as i64))?;
+
+    let intervals = connection.view_presence_intervals(start, end)?;
+    if intervals.is_empty() {
+        println!("No events detected between {start} and {end}.");
+    }
+    let mut days: Vec<(NaiveDate, Vec<&PresenceInterval>)> = Vec::new();
+    for interval in &intervals {
+        match days.last_mut() {
+            Some((date, day)) if *date == interval.date => { day.push(interval); }
+            _ => { days.push((interval.date, vec![interval])); }
+        }
+    }
+    for (date, day) in days {
+        let stays: Vec<String> = day.iter().map(|interval| interval.to_string()).collect();
+        println!("{} {}", date.format("%a %Y-%m-%d"), stays.join(", "));
+    }
+    Ok(())
+}
+
+pub fn execute_export(config: ApplicationConfig, mut connection: Connection, options: &ExportOptions) -> anyhow::Result<()> {
+    connection.update_max_gap(Duration::seconds(config.timeline.max_gap_seconds as i64))?;
+    let rows = connection.list_presence_intervals()?;
+    export(&rows, options.format, &options.output, &config.export)?;
+    Ok(())
+}
+
+/// a row of an import, either a presence interval of `event export` or a single detection
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ImportRow {
+    Interval(PresenceInterval),
+    Event(Event),
 }
 
 pub fn execute_import(_config: ApplicationConfig, mut connection: Connection) {
-    let rows: Vec<Event> = serde_json::from_reader(io::stdin()).expect("Could not read JSON from stdin!");
+    let rows: Vec<ImportRow> = serde_json::from_reader(io::stdin()).expect("Could not read JSON from stdin!");
     debug!("Read data: {:?}", rows);
 
     for row in rows {
-        connection.insert_event(&row)
-            .err()
-            .map(|e| warn!("Could not insert row: {:?}; Error: {:?}", row, e));
+        let result = match &row {
+            ImportRow::Interval(interval) => connection.insert_location_intervals(std::slice::from_ref(interval)),
+            ImportRow::Event(event) => connection.insert_event(event),
+        };
+        if let Err(e) = result {
+            warn!("Could not insert row: {:?}; Error: {:?}", row, e);
+        }
     }
 }
-
-
-
-
-
-
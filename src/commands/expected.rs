@@ -0,0 +1,93 @@
+use anyhow::anyhow;
+use chrono::{Datelike, Local, NaiveDate};
+use cli_table::{Cell, print_stdout, Style, Table};
+use rusqlite::Connection;
+use crate::args::ExpectedCommand;
+use crate::commands::toggl::update_expected_durations;
+use crate::config::ApplicationConfig;
+use crate::datastore::DataStore;
+use crate::dates::{parse_date_range, parse_duration};
+use crate::duration_newtype::Duration;
+use crate::ErrorHandler;
+use crate::models::ExpectedDuration;
+use crate::table_cli_helper::TableFormatter;
+
+/// marks expected durations set by the user, they are kept when the schedule is refreshed
+const MANUAL_SOURCE: &str = "manual";
+
+pub fn main(config: &ApplicationConfig, command: &ExpectedCommand, connection: &mut Connection) {
+    match command {
+        ExpectedCommand::Set { range, duration } => {
+            execute_set(config, connection, range, duration).handle_error();
+        }
+        ExpectedCommand::List { range, all } => {
+            execute_list(connection, range, *all).handle_error();
+        }
+        ExpectedCommand::Reset { range } => {
+            execute_reset(config, connection, range).handle_error();
+        }
+    }
+}
+
+pub fn execute_set(config: &ApplicationConfig, connection: &mut Connection, range: &str, duration: &str) -> anyhow::Result<()> {
+    let (start, end) = parse_date_range(range)?;
+    let duration = parse_duration(duration)
+        .filter(|duration| *duration >= chrono::Duration::zero())
+        .ok_or(anyhow!("Invalid duration '{duration}', use something like 4h30m"))?;
+
+    // a single day is always changed, in a range days off are kept free
+    let dates: Vec<NaiveDate> = start
+        .iter_days()
+        .take_while(|date| *date <= end)
+        .filter(|date| start == end || config.workweek.expected_duration(*date).num_seconds() > 0)
+        .collect();
+    for date in &dates {
+        connection.insert_expected_duration(ExpectedDuration {
+            date: *date,
+            duration: Duration::of(duration),
+            source: MANUAL_SOURCE.to_string(),
+        })?;
+    }
+    println!("Expecting {} on {} days.", Duration::of(duration).format_hours_minutes(), dates.len());
+    Ok(())
+}
+
+pub fn execute_list(connection: &mut Connection, range: &Option<String>, all: bool) -> anyhow::Result<()> {
+    let (start, end) = match range {
+        None => {
+            let year = Local::now().year();
+            (NaiveDate::from_ymd_opt(year, 1, 1).unwrap(), NaiveDate::from_ymd_opt(year, 12, 31).unwrap())
+        }
+        Some(range) => { parse_date_range(range)? }
+    };
+    let expected_durations: Vec<ExpectedDuration> = connection
+        .list_expected_durations(start, end)?
+        .into_iter()
+        .filter(|expected_duration| all || expected_duration.source != "schedule")
+        .collect();
+
+    let table = expected_durations
+        .iter()
+        .map(|x| x.into())
+        .collect::<Vec<Vec<cli_table::CellStruct>>>()
+        .table()
+        .title(vec![
+            "Date".cell().bold(true),
+            "Day".cell().bold(true),
+            "Expected".cell().bold(true),
+            "Source".cell().bold(true),
+        ])
+        .format_table();
+    print_stdout(table)?;
+    Ok(())
+}
+
+pub fn execute_reset(config: &ApplicationConfig, connection: &mut Connection, range: &str) -> anyhow::Result<()> {
+    let (start, end) = parse_date_range(range)?;
+    let removed = connection.remove_expected_duration_overrides(start, end)?;
+
+    // the schedule fills the gaps again
+    update_expected_durations(config, connection, Local::now().date_naive())?;
+    println!("Removed {removed} overrides.");
+    Ok(())
+}
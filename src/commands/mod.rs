@@ -1,4 +1,14 @@
 pub mod probe;
 pub mod detect;
 pub mod event;
-pub mod toggl;
\ No newline at end of file
+pub mod toggl;
+pub mod absence;
+pub mod track;
+pub mod report;
+pub mod timesheet;
+pub mod backup;
+pub mod time;
+pub mod expected;
+pub mod saldo;
+pub mod daemon;
+pub mod db;
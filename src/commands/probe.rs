@@ -1,6 +1,10 @@
 use std::collections::HashMap;
 use crate::args::ProbeCommand;
+use anyhow::anyhow;
 use crate::config::{ApplicationConfig, Probe};
+use crate::ErrorHandler;
+use crate::probe::run_probe;
+use crate::rules::compile;
 
 pub fn main(config: &mut ApplicationConfig, sub_command: &ProbeCommand) {
     match sub_command {
@@ -13,6 +17,13 @@ pub fn main(config: &mut ApplicationConfig, sub_command: &ProbeCommand) {
             }
         }
         ProbeCommand::Remove { event } => {
+            // a rule using the probe would make the configuration invalid
+            let mut probes = config.probes.clone();
+            probes.remove(event);
+            if let Err(error) = compile(&config.rules, &probes) {
+                println!("Could not remove probe! {error}");
+                return;
+            }
             let result = config.remove_probe(event.to_string());
             if result.is_err() {
                 println!("Could not remove probe! {:?}", result);
@@ -34,5 +45,24 @@ pub fn main(config: &mut ApplicationConfig, sub_command: &ProbeCommand) {
             }).expect("Could not serialize to toml");
             println!("{toml}");
         }
+        ProbeCommand::Test { event } => {
+            execute_test(config, event).handle_error();
+        }
+    }
+}
+
+pub fn execute_test(config: &ApplicationConfig, name: &str) -> anyhow::Result<()> {
+    let probe = config.probes
+        .get(name)
+        .ok_or(anyhow!("There is no probe named {name}"))?;
+    println!("Checking {}", probe.kind);
+
+    let outcome = run_probe(name, probe);
+    println!("{outcome}");
+    for (stream, output) in [("stdout", &outcome.stdout), ("stderr", &outcome.stderr)] {
+        if !output.trim().is_empty() {
+            println!("--- {stream} ---\n{}", output.trim_end());
+        }
     }
+    Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,66 @@
+use chrono::NaiveDate;
+use cli_table::{Cell, print_stdout, Style, Table};
+use rusqlite::Connection;
+use crate::datastore::DataStore;
+use crate::dates::Period;
+use crate::ErrorHandler;
+use crate::report::{aggregate, ReportFormat, ReportGrouping, ReportRow};
+use crate::table_cli_helper::TableFormatter;
+
+pub fn main(
+    connection: &mut Connection,
+    grouping: ReportGrouping,
+    period: Option<Period>,
+    format: ReportFormat,
+    start: NaiveDate,
+    end: NaiveDate)
+{
+    execute_report(connection, grouping, period, format, start, end).handle_error();
+}
+
+pub fn execute_report(
+    connection: &mut Connection,
+    grouping: ReportGrouping,
+    period: Option<Period>,
+    format: ReportFormat,
+    start: NaiveDate,
+    end: NaiveDate) -> anyhow::Result<()>
+{
+    let entries = connection.list_time_entry_details(start, end)?;
+    let rows = aggregate(&entries, grouping, period);
+
+    match format {
+        ReportFormat::Table => {
+            print_table(&rows, grouping)?;
+        }
+        ReportFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&rows)?);
+        }
+        ReportFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout());
+            for row in &rows {
+                writer.serialize(row)?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+fn print_table(rows: &[ReportRow], grouping: ReportGrouping) -> anyhow::Result<()> {
+    let table = rows
+        .iter()
+        .map(|x| x.into())
+        .collect::<Vec<Vec<cli_table::CellStruct>>>()
+        .table()
+        .title(vec![
+            "Period".cell().bold(true),
+            grouping.title().cell().bold(true),
+            "Duration".cell().bold(true),
+            "Share".cell().bold(true),
+        ])
+        .format_table();
+
+    print_stdout(table)?;
+    Ok(())
+}
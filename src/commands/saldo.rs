@@ -0,0 +1,93 @@
+use anyhow::anyhow;
+use chrono::{Datelike, Local, NaiveDate};
+use cli_table::{Cell, print_stdout, Style, Table};
+use rusqlite::Connection;
+use crate::args::SaldoCommand;
+use crate::commands::toggl::update_expected_durations;
+use crate::config::ApplicationConfig;
+use crate::datastore::DataStore;
+use crate::dates::{parse_date, parse_duration};
+use crate::duration_newtype::Duration;
+use crate::ErrorHandler;
+use crate::models::SaldoAdjustment;
+use crate::table_cli_helper::TableFormatter;
+
+pub fn main(config: &ApplicationConfig, command: &SaldoCommand, connection: &mut Connection) {
+    match command {
+        SaldoCommand::Adjust { date, amount, reason } => {
+            execute_adjust(config, connection, date, amount, reason).handle_error();
+        }
+        SaldoCommand::List { year } => {
+            let year = year.unwrap_or(Local::now().year());
+            execute_list(config, connection, year).handle_error();
+        }
+        SaldoCommand::Remove { id } => {
+            execute_remove(config, connection, *id).handle_error();
+        }
+    }
+}
+
+pub fn execute_adjust(
+    config: &ApplicationConfig,
+    connection: &mut Connection,
+    date: &str,
+    amount: &str,
+    reason: &str) -> anyhow::Result<()>
+{
+    let date = parse_date(date)?;
+    let amount = parse_duration(amount)
+        .ok_or(anyhow!("Invalid amount '{amount}', use something like 10h or -4h30m"))?;
+    let id = connection.insert_saldo_adjustment(&SaldoAdjustment {
+        id: 0,
+        date,
+        amount: Duration::of(amount),
+        reason: reason.to_string(),
+        source: "manual".to_string(),
+    })?;
+
+    // a manual adjustment changes the saldo the carry-over cap applies to
+    let today = Local::now().date_naive();
+    update_expected_durations(config, connection, today)?;
+    println!(
+        "Adjusted the saldo by {} (id {id}), the saldo is now {}.",
+        Duration::of(amount).format_hours_minutes(),
+        Duration::of(connection.view_saldo(today)?).format_hours_minutes()
+    );
+    Ok(())
+}
+
+pub fn execute_list(config: &ApplicationConfig, connection: &mut Connection, year: i32) -> anyhow::Result<()> {
+    let start = NaiveDate::from_ymd_opt(year, 1, 1).unwrap();
+    let end = NaiveDate::from_ymd_opt(year, 12, 31).unwrap();
+    let today = Local::now().date_naive();
+    update_expected_durations(config, connection, today)?;
+
+    let table = connection
+        .list_saldo_adjustments(start, end)?
+        .iter()
+        .map(|x| x.into())
+        .collect::<Vec<Vec<cli_table::CellStruct>>>()
+        .table()
+        .title(vec![
+            "Id".cell().bold(true),
+            "Date".cell().bold(true),
+            "Amount".cell().bold(true),
+            "Reason".cell().bold(true),
+            "Source".cell().bold(true),
+        ])
+        .format_table();
+    print_stdout(table)?;
+
+    let date = end.min(today);
+    println!("Saldo on {date}: {}", Duration::of(connection.view_saldo(date)?).format_hours_minutes());
+    Ok(())
+}
+
+pub fn execute_remove(config: &ApplicationConfig, connection: &mut Connection, id: i64) -> anyhow::Result<()> {
+    if connection.remove_saldo_adjustment(id)? == 0 {
+        return Err(anyhow!("There is no manual adjustment with id {id}"));
+    }
+    update_expected_durations(config, connection, Local::now().date_naive())?;
+    println!("Removed adjustment {id}.");
+    Ok(())
+}
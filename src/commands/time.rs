@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+
+use chrono::{Duration, Local};
+use log::warn;
+use rusqlite::Connection;
+
+use crate::args::TimeCommand;
+use crate::config::ApplicationConfig;
+use crate::datastore::DataStore;
+use crate::ErrorHandler;
+use crate::import::{ImportFormat, read_csv, split_overlapping};
+use crate::models::TimeEntry;
+
+pub fn main(config: &ApplicationConfig, command: &TimeCommand, connection: &mut Connection) {
+    match command {
+        TimeCommand::Import { path, format, allow_overlaps } => {
+            execute_import(config, connection, path, *format, *allow_overlaps).handle_error();
+        }
+    }
+}
+
+/// Imports time entries from a csv file.
+/// Time entries imported before from the same source are updated instead of duplicated.
+pub fn execute_import(
+    config: &ApplicationConfig,
+    connection: &mut Connection,
+    path: &Path,
+    format: ImportFormat,
+    allow_overlaps: bool) -> anyhow::Result<()>
+{
+    let mapping = format.mapping(&config.import.columns);
+    let entries = read_csv(File::open(path)?, &mapping, config.import.delimiter)?;
+    let source = format.source();
+    let imported_ids = connection.list_imported_time_entry_ids(source)?;
+
+    // time entries from an earlier import of the same file are replaced and do not count as overlap
+    let (accepted, overlapping) = match (entries.iter().map(|entry| entry.start).min(), entries.iter().map(|entry| entry.stop).max()) {
+        (Some(first), Some(last)) if !allow_overlaps => {
+            let reimported: Vec<i64> = entries
+                .iter()
+                .filter_map(|entry| imported_ids.get(&entry.external_id).copied())
+                .collect();
+            let existing: Vec<TimeEntry> = connection
+                .list_time_entry_details(
+                    first.with_timezone(&Local).date_naive() - Duration::days(1),
+                    last.with_timezone(&Local).date_naive())?
+                .into_iter()
+                .map(|details| details.time_entry)
+                .filter(|time_entry| !reimported.contains(&time_entry.id))
+                .collect();
+            split_overlapping(entries, &existing)
+        }
+        _ => { (entries, vec![]) }
+    };
+
+    for entry in &overlapping {
+        warn!(
+            "Line {}: skipping {} - {} {}, it overlaps another time entry",
+            entry.line,
+            entry.start.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+            entry.stop.with_timezone(&Local).format("%H:%M"),
+            entry.description.clone().unwrap_or_default()
+        );
+    }
+
+    let mut next_id = connection.next_local_time_entry_id()?;
+    let mut projects: HashMap<String, i64> = HashMap::new();
+    let mut time_entries = Vec::new();
+    let mut updated = 0;
+    for entry in accepted {
+        let id = match imported_ids.get(&entry.external_id) {
+            Some(id) => {
+                updated += 1;
+                *id
+            }
+            None => {
+                let id = next_id;
+                next_id -= 1;
+                id
+            }
+        };
+        let project_id = match &entry.project {
+            None => { None }
+            Some(name) => {
+                if !projects.contains_key(name) {
+                    projects.insert(name.clone(), connection.find_or_insert_local_project(name)?);
+                }
+                projects.get(name).copied()
+            }
+        };
+
+        time_entries.push((entry.external_id, TimeEntry {
+            id,
+            description: entry.description,
+            start: entry.start,
+            stop: Some(entry.stop),
+            project_id,
+            workspace_id: None,
+            billable: entry.billable,
+            tags: entry.tags,
+        }));
+    }
+    connection.insert_imported_time_entries(source, &time_entries)?;
+
+    println!(
+        "Imported {} time entries ({} of them updated), skipped {} overlapping ones.",
+        time_entries.len(),
+        updated,
+        overlapping.len()
+    );
+    Ok(())
+}
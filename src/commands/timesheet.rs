@@ -0,0 +1,72 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::bail;
+use chrono::{Local, NaiveDate};
+use rusqlite::Connection;
+
+use crate::args::TimesheetCommand;
+use crate::commands::toggl::update_expected_durations;
+use crate::config::ApplicationConfig;
+use crate::datastore::DataStore;
+use crate::dates::Period;
+use crate::ErrorHandler;
+use crate::printout::{convert_to_pdf, find_pdf_command, load_template, PrintError, render_month};
+
+pub fn main(config: &ApplicationConfig, command: &TimesheetCommand, connection: &mut Connection) {
+    match command {
+        TimesheetCommand::Print { month, output, no_pdf } => {
+            execute_print(config, connection, month, output, no_pdf).handle_error();
+        }
+    }
+}
+
+/// writes the working time record of a month as html and converts it to pdf
+pub fn execute_print(
+    config: &ApplicationConfig,
+    connection: &mut Connection,
+    month: &Option<String>,
+    output: &Option<PathBuf>,
+    no_pdf: &bool) -> anyhow::Result<()>
+{
+    let today = Local::now().date_naive();
+    let month = match month {
+        None => { today }
+        Some(month) => {
+            match NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d") {
+                Ok(date) => { date }
+                Err(_) => { bail!("Invalid month '{month}', please use the format YYYY-MM!") }
+            }
+        }
+    };
+    let start = Period::Month.start_of(month);
+    let end = Period::Month.end_of(month);
+
+    update_expected_durations(config, connection, end.min(today))?;
+    let timesheet = connection.view_full_timesheet(start, end)?;
+    let holidays = connection.list_holidays(start, end)?;
+    let absences = connection.list_absences(start, end)?;
+
+    let template = load_template(ApplicationConfig::template_path().as_deref())?;
+    let employee = config.print.employee.clone().unwrap_or_default();
+    let html = render_month(&template, &employee, month, &timesheet, &holidays, &absences);
+
+    let path = output
+        .clone()
+        .unwrap_or(PathBuf::from(format!("timesheet-{}.html", start.format("%Y-%m"))));
+    fs::write(&path, html)?;
+    println!("Wrote {}", path.display());
+
+    if *no_pdf {
+        return Ok(());
+    }
+    let pdf_command = config.print.pdf_command
+        .clone()
+        .or_else(|| find_pdf_command(&env::var_os("PATH").unwrap_or_default()))
+        .ok_or(PrintError::NoPdfCommand)?;
+    let pdf_path = path.with_extension("pdf");
+    convert_to_pdf(&pdf_command, &path, &pdf_path)?;
+    println!("Wrote {}", pdf_path.display());
+    Ok(())
+}
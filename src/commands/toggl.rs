@@ -1,17 +1,22 @@
 
-use chrono::{Duration, NaiveDate, Utc};
+use chrono::{Datelike, DateTime, Duration, Local, NaiveDate, Utc};
 use cli_table::{WithTitle};
 use cli_table::format::{VerticalLine};
-use log::{debug, error};
+use colored::Colorize;
+use log::{debug, error, warn};
 use rusqlite::Connection;
-use crate::args::{TogglCommand};
+use crate::args::{ExportOptions, TogglCommand};
 use crate::config;
 use crate::config::{ApplicationConfig};
 use crate::datastore::DataStore;
-use crate::dates::parse_time_interval;
+use crate::dates::{parse_date, parse_time_interval, Period};
+use crate::ErrorHandler;
+use crate::export::export;
+use crate::holidays::holidays_between;
+use crate::models::{rollup, TimeSheet};
 
 
-use crate::toggl::get_time_entries;
+use crate::toggl::{get_clients, get_projects, get_tags, get_time_entries, get_time_entries_since, get_workspaces};
 use crate::table_cli_helper::TableFormatter;
 
 pub fn main(config: &mut ApplicationConfig, command: &crate::args::TogglCommand, connection: &mut Connection) {
@@ -19,30 +24,43 @@ pub fn main(config: &mut ApplicationConfig, command: &crate::args::TogglCommand,
         TogglCommand::Token { token } => {
             crate::commands::toggl::execute_token(config, token);
         }
-        TogglCommand::Show { compact, start, end } => {
-            match &config.toggl {
+        TogglCommand::Show { compact, offline, group_by, start, end } => {
+            let (start, end) = parse_time_interval(start, end);
+            execute_show(config, connection, *compact, *offline, *group_by, start.date_naive(), end.date_naive());
+        }
+        TogglCommand::Sync { from, to } => {
+            match toggl_access(config) {
                 None => {
                     error!("There is no toggl access configured!")
                 }
-                Some(_toggl) => {
-                    let (start, end) = parse_time_interval(start, end);
-                    execute_show(config, connection, *compact, start.date_naive(), end.date_naive());
+                Some(toggl) => {
+                    let from = from.as_deref().map(parse_date).transpose().handle_error();
+                    let to = to.as_deref().map(parse_date).transpose().handle_error();
+                    let summary = sync_time_entries(toggl, connection, from, to).handle_error();
+                    sync_toggl_metadata(toggl, connection).handle_error();
+                    println!("Synchronized {} time entries and removed {} deleted ones.", summary.updated, summary.deleted);
+                    if summary.unreadable > 0 {
+                        println!("{}", format!("{} time entries from toggl could not be read and were left unchanged.", summary.unreadable).yellow());
+                    }
                 }
             }
         }
-        TogglCommand::Export { .. } => {
-            let timesheet = connection.view_timesheet_export().unwrap();
-            let json = serde_json::to_string_pretty(&timesheet).expect("Could not serialize to json!");
-            println!("{}", json);
+        TogglCommand::Entries { start, end } => {
+            let (start, end) = parse_time_interval(start, end);
+            execute_entries(connection, start.date_naive(), end.date_naive()).handle_error();
+        }
+        TogglCommand::Export { group_by, options } => {
+            execute_export(config, connection, *group_by, options).handle_error();
         }
     }
 }
 
-pub fn execute_token(config: &mut ApplicationConfig, token: &String) {
-    // TODO: might override future attributes
+pub fn execute_token(config: &mut ApplicationConfig, token: &str) {
+    let api_url = config.toggl.as_ref().and_then(|toggl| toggl.api_url.clone());
     config.toggl = Some(config::Toggl {
-        username: token.clone(),
+        username: token.to_string(),
         password: "api_token".to_string(),
+        api_url,
     });
     match config.save_config() {
         Ok(_) => {
@@ -54,40 +72,253 @@ pub fn execute_token(config: &mut ApplicationConfig, token: &String) {
     }
 }
 
+/// key of the setting which stores the instant of the last successful sync
+static LAST_SYNC_SETTING: &str = "toggl.last_sync";
+
+/// toggl only reports changes and deletions of the last three months
+const MAX_INCREMENTAL_SYNC_DAYS: i64 = 90;
+
+pub struct SyncSummary {
+    pub updated: usize,
+    pub deleted: usize,
+    /// time entries toggl sent which could not be read, they are kept unchanged
+    pub unreadable: usize,
+}
+
+/// returns the toggl configuration if an api token is set
+fn toggl_access(config: &ApplicationConfig) -> Option<&config::Toggl> {
+    config.toggl.as_ref().filter(|toggl| !toggl.username.is_empty())
+}
+
+fn view_last_sync(connection: &mut Connection) -> anyhow::Result<Option<DateTime<Utc>>> {
+    Ok(connection.view_setting(LAST_SYNC_SETTING)?
+        .map(|value| DateTime::parse_from_rfc3339(&value))
+        .transpose()?
+        .map(|instant| instant.with_timezone(&Utc)))
+}
+
+/// Downloads all time entries which changed since the last sync and removes the ones deleted in toggl.
+/// If there was no sync yet or it is too long ago, a window of time entries is downloaded instead.
+/// If from is given all time entries between from and to are downloaded, without touching the sync cursor.
+pub fn sync_time_entries(
+    toggl: &config::Toggl,
+    connection: &mut Connection,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>) -> anyhow::Result<SyncSummary>
+{
+    let sync_start = Utc::now();
+    let last_sync = view_last_sync(connection)?;
+
+    let summary = match (from, last_sync) {
+        (None, Some(last_sync)) if sync_start - last_sync < Duration::days(MAX_INCREMENTAL_SYNC_DAYS) => {
+            debug!("Downloading changes since {last_sync}");
+            let changes = get_time_entries_since(toggl, &last_sync)?;
+            connection.insert_time_entries(&changes.updated)?;
+            SyncSummary {
+                updated: changes.updated.len(),
+                deleted: connection.remove_time_entries(&changes.deleted)?,
+                unreadable: changes.unreadable.len(),
+            }
+        }
+        _ => {
+            let today = sync_start.date_naive();
+            let start = from
+                .or(last_sync.map(|last_sync| last_sync.date_naive()))
+                .unwrap_or(today - Duration::weeks(9));
+            let end = to.unwrap_or(today) + Duration::days(1);
+            debug!("Downloading all time entries from {start} to {end}");
+
+            let window = get_time_entries(toggl, &start, &end)?;
+            connection.insert_time_entries(&window.time_entries)?;
+
+            // everything in the window toggl did not return has been deleted there
+            SyncSummary {
+                updated: window.time_entries.len(),
+                deleted: connection.remove_toggl_time_entries_except(start, end, &window.ids)?,
+                unreadable: window.unreadable.len(),
+            }
+        }
+    };
+
+    if from.is_none() {
+        connection.update_setting(LAST_SYNC_SETTING, &sync_start.to_rfc3339())?;
+    }
+    Ok(summary)
+}
+
+/// downloads the workspaces, projects, clients and tags of all workspaces
+pub fn sync_toggl_metadata(toggl: &config::Toggl, connection: &mut Connection) -> anyhow::Result<()> {
+    let workspaces = get_workspaces(toggl)?;
+    for workspace in &workspaces {
+        connection.insert_projects(&get_projects(toggl, workspace.id)?)?;
+        connection.insert_clients(&get_clients(toggl, workspace.id)?)?;
+        connection.insert_tags(&get_tags(toggl, workspace.id)?)?;
+    }
+    connection.insert_workspaces(&workspaces)?;
+    debug!("Synchronized {} workspaces.", workspaces.len());
+    Ok(())
+}
+
+/// lists all time entries with the names of their workspace, client and project
+pub fn execute_entries(connection: &mut Connection, start: NaiveDate, end: NaiveDate) -> anyhow::Result<()> {
+    use cli_table::{Cell, Style, Table};
+
+    let table = connection.list_time_entry_details(start, end)?
+        .iter()
+        .map(|x| x.into())
+        .collect::<Vec<Vec<cli_table::CellStruct>>>()
+        .table()
+        .title(vec![
+            "Start".cell().bold(true),
+            "Stop".cell().bold(true),
+            "Duration".cell().bold(true),
+            "Workspace".cell().bold(true),
+            "Client".cell().bold(true),
+            "Project".cell().bold(true),
+            "Description".cell().bold(true),
+            "Tags".cell().bold(true),
+            "Billable".cell().bold(true),
+        ])
+        .format_table();
+
+    cli_table::print_stdout(table)?;
+    Ok(())
+}
+
+/// exports the whole timesheet, optionally summed up per period
+pub fn execute_export(
+    config: &ApplicationConfig,
+    connection: &mut Connection,
+    group_by: Option<Period>,
+    options: &ExportOptions) -> anyhow::Result<()>
+{
+    let mut timesheet = connection.view_timesheet_export()?;
+    timesheet.iter_mut().for_each(|row| row.apply_breaks(&config.breaks));
+    match group_by {
+        None => {
+            export(&timesheet, options.format, &options.output, &config.export)?;
+        }
+        Some(period) => {
+            let rollup = rollup(&timesheet, period, Local::now().date_naive());
+            export(&rollup, options.format, &options.output, &config.export)?;
+        }
+    }
+    Ok(())
+}
+
+/// tells the user how old the shown time entries from toggl are
+fn print_last_sync_notice(connection: &mut Connection) {
+    let notice = match view_last_sync(connection).ok().flatten() {
+        None => {
+            "Offline: time entries from toggl have never been synchronized.".to_string()
+        }
+        Some(last_sync) => {
+            format!("Offline: data as of {}.", last_sync.with_timezone(&Local).format("%Y-%m-%d %H:%M"))
+        }
+    };
+    println!("{}", notice.yellow());
+}
+
+/// refreshes the holidays and the expected durations from the schedule until the given date
+pub fn update_expected_durations(config: &ApplicationConfig, connection: &mut Connection, until: NaiveDate) -> anyhow::Result<()> {
+    connection.update_max_gap(Duration::seconds(config.timeline.max_gap_seconds as i64))?;
+    let initial_saldo = Duration::seconds(config.saldo.initial_saldo_seconds.unwrap_or_default());
+    connection.update_tracking_start(config.saldo.tracking_start, initial_saldo)?;
+
+    if let Some(first_date) = connection.view_tracking_start()? {
+        let end_of_year = NaiveDate::from_ymd_opt(until.year(), 12, 31).unwrap_or(until);
+        let holidays = match &config.holidays {
+            None => { vec![] }
+            Some(holidays) => { holidays_between(holidays, first_date, end_of_year)? }
+        };
+        connection.replace_holidays(&holidays)?;
+    }
+    connection.insert_scheduled_expected_durations(&config.workweek, until)?;
+
+    let cap = config.saldo.carry_over_cap_seconds.map(|seconds| Duration::seconds(seconds as i64));
+    connection.update_carry_over_cap(cap, until)
+}
+
 pub fn execute_show(
     config: &ApplicationConfig,
     connection: &mut Connection,
     compact: bool,
+    offline: bool,
+    group_by: Option<Period>,
     show_start: NaiveDate,
     show_stop: NaiveDate)
 {
-    let toggl = config.toggl.as_ref().unwrap();
     let now = Utc::now().date_naive();
-    let start = now - Duration::weeks(9);
-    let end = now + Duration::days(1);
-    let result = get_time_entries(toggl, &start, &end)
-        .expect("Could not access the toggl API!");
-
-    debug!("Got all time entries!");
 
-    connection.insert_time_entries(&result)
-        .expect("Could not save time entry!");
-
-    debug!("Saved all time entries!");
-    let default_expected = Duration::seconds(config.workweek.default_expected_duration_seconds as i64);
-    connection.insert_default_expected_duration(default_expected).unwrap();
+    // without toggl only the locally tracked time entries are shown
+    match toggl_access(config) {
+        None => {
+            debug!("There is no toggl access configured, showing local time entries only.");
+        }
+        Some(_) if offline => {
+            print_last_sync_notice(connection);
+        }
+        Some(toggl) => {
+            let synced = sync_time_entries(toggl, connection, None, None)
+                .and_then(|_| match connection.view_missing_toggl_metadata()? {
+                    true => { sync_toggl_metadata(toggl, connection) }
+                    false => { Ok(()) }
+                });
+            match synced {
+                Ok(_) => {
+                    debug!("Saved all time entries!");
+                }
+                Err(error) => {
+                    warn!("Could not access the toggl API: {error}");
+                    print_last_sync_notice(connection);
+                }
+            }
+        }
+    }
+    update_expected_durations(config, connection, now)
+        .expect("Could not save expected durations!");
 
-    let timesheet = if compact {
+    let mut timesheet = if compact {
         connection.view_timesheet(show_start, show_stop)
     } else {
         connection.view_full_timesheet(show_start, show_stop)
     }.unwrap();
+    timesheet.iter_mut().for_each(|row| row.apply_breaks(&config.breaks));
 
     let _vertical_line = VerticalLine::new('│');
 
+    match group_by {
+        None => {
+            let table = timesheet.with_title().format_table();
+            assert!(cli_table::print_stdout(table).is_ok());
+        }
+        Some(period) => {
+            print_rollup(&timesheet, period).handle_error();
+        }
+    }
+}
+
+/// prints the timesheet summed up per period with the current period in bold
+fn print_rollup(timesheet: &TimeSheet, period: Period) -> anyhow::Result<()> {
+    use cli_table::{Cell, Style, Table};
 
-    let table = timesheet.with_title().format_table();
+    let table = rollup(timesheet, period, Local::now().date_naive())
+        .iter()
+        .map(|x| x.into())
+        .collect::<Vec<Vec<cli_table::CellStruct>>>()
+        .table()
+        .title(vec![
+            "Period".cell().bold(true),
+            "From".cell().bold(true),
+            "To".cell().bold(true),
+            "Actual".cell().bold(true),
+            "Expected".cell().bold(true),
+            "Delta".cell().bold(true),
+            "Saldo".cell().bold(true),
+        ])
+        .format_table();
 
-    assert!(cli_table::print_stdout(table).is_ok());
+    cli_table::print_stdout(table)?;
+    Ok(())
 }
 
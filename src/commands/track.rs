@@ -0,0 +1,104 @@
+use anyhow::bail;
+use chrono::{Local, Utc};
+use rusqlite::Connection;
+use crate::args::TrackCommand;
+use crate::datastore::DataStore;
+use crate::dates::parse_date_time;
+use crate::duration_newtype::Duration;
+use crate::ErrorHandler;
+use crate::models::TimeEntry;
+
+pub fn main(command: &TrackCommand, connection: &mut Connection) {
+    match command {
+        TrackCommand::Start { description } => {
+            execute_start(connection, description).handle_error();
+        }
+        TrackCommand::Stop {} => {
+            execute_stop(connection).handle_error();
+        }
+        TrackCommand::Status {} => {
+            execute_status(connection).handle_error();
+        }
+        TrackCommand::Add { from, to, description } => {
+            execute_add(connection, from, to, description).handle_error();
+        }
+    }
+}
+
+pub fn execute_start(connection: &mut Connection, description: &Option<String>) -> anyhow::Result<()> {
+    if let Some(running) = connection.view_running_time_entry()? {
+        bail!("There is already a running time entry since {}!", running.start.with_timezone(&Local).format("%H:%M"));
+    }
+
+    let time_entry = TimeEntry {
+        id: connection.next_local_time_entry_id()?,
+        description: description.clone(),
+        start: Utc::now(),
+        stop: None,
+        project_id: None,
+        workspace_id: None,
+        billable: false,
+        tags: vec![],
+    };
+    connection.insert_time_entry(&time_entry)?;
+    println!("Started tracking at {}.", time_entry.start.with_timezone(&Local).format("%H:%M"));
+    Ok(())
+}
+
+pub fn execute_stop(connection: &mut Connection) -> anyhow::Result<()> {
+    match connection.view_running_time_entry()? {
+        None => {
+            bail!("There is no running time entry!");
+        }
+        Some(mut time_entry) => {
+            if time_entry.id > 0 {
+                bail!("The running time entry belongs to toggl, please stop it there.");
+            }
+            time_entry.stop = Some(Utc::now());
+            connection.insert_time_entry(&time_entry)?;
+            let duration = time_entry.duration().unwrap_or_default();
+            println!("Stopped tracking after {}.", duration.format_unsigned());
+        }
+    }
+    Ok(())
+}
+
+pub fn execute_status(connection: &mut Connection) -> anyhow::Result<()> {
+    match connection.view_running_time_entry()? {
+        None => {
+            println!("Not tracking.");
+        }
+        Some(time_entry) => {
+            let duration = Duration::of(Utc::now() - time_entry.start);
+            println!(
+                "Tracking {}since {} ({}).",
+                time_entry.description.map(|description| format!("'{description}' ")).unwrap_or_default(),
+                time_entry.start.with_timezone(&Local).format("%H:%M"),
+                duration.format_unsigned()
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn execute_add(connection: &mut Connection, from: &str, to: &str, description: &Option<String>) -> anyhow::Result<()> {
+    let start = parse_date_time(from)?;
+    let stop = parse_date_time(to)?;
+    if stop <= start {
+        bail!("The end of the time entry has to be after its begin!");
+    }
+
+    let time_entry = TimeEntry {
+        id: connection.next_local_time_entry_id()?,
+        description: description.clone(),
+        start,
+        stop: Some(stop),
+        project_id: None,
+        workspace_id: None,
+        billable: false,
+        tags: vec![],
+    };
+    connection.insert_time_entry(&time_entry)?;
+    println!("Added {} of work.", time_entry.duration().unwrap_or_default().format_unsigned());
+    Ok(())
+}
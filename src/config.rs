@@ -2,36 +2,368 @@ use std::collections::HashMap;
 
 
 use std::fs;
+use std::net::Ipv4Addr;
 use std::path::{PathBuf};
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime, Weekday};
 use colored::Colorize;
 use confy::ConfyError;
 use directories::ProjectDirs;
 use log::debug;
 use serde_derive::{Deserialize, Serialize};
+use crate::rules::compile;
 
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct ApplicationConfig {
     pub probes: HashMap<String, Probe>,
+    /// classifications of the workplace from the probes, the first matching rule wins
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<Rule>,
     pub toggl: Option<Toggl>,
     pub workweek: WorkWeek,
+    pub holidays: Option<Holidays>,
+    pub vacation: Option<Vacation>,
+    #[serde(default)]
+    pub breaks: Breaks,
+    #[serde(default)]
+    pub export: Export,
+    #[serde(default)]
+    pub print: Print,
+    #[serde(default)]
+    pub backup: Backup,
+    #[serde(default)]
+    pub import: Import,
+    #[serde(default)]
+    pub saldo: Saldo,
+    #[serde(default)]
+    pub daemon: Daemon,
+    #[serde(default)]
+    pub timeline: Timeline,
+    #[serde(default)]
+    pub retention: Retention,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Toggl {
     pub username: String,
     pub password: String,
+    /// base url of the toggl api, defaults to https://api.track.toggl.com
+    pub api_url: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct WorkWeek {
+    /// expected duration on every weekday from monday to friday if no schedule is given
     pub default_expected_duration_seconds: u64,
+
+    /// expected duration per weekday
+    pub schedule: Option<WeeklySchedule>,
+
+    /// schedules which replace the default schedule beginning with a certain date
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub periods: Vec<SchedulePeriod>,
+}
+
+/// Expected duration in seconds per weekday.
+/// A missing weekday is a day off.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct WeeklySchedule {
+    pub monday: Option<u64>,
+    pub tuesday: Option<u64>,
+    pub wednesday: Option<u64>,
+    pub thursday: Option<u64>,
+    pub friday: Option<u64>,
+    pub saturday: Option<u64>,
+    pub sunday: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SchedulePeriod {
+    /// first day this schedule is valid
+    pub from: NaiveDate,
+    pub schedule: WeeklySchedule,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Holidays {
+    /// german federal state whose public holidays are used, eg. "BY" or "NW"
+    pub region: Option<String>,
+
+    /// additional holidays from iCalendar (*.ics) or csv files (date,name)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<PathBuf>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Breaks {
+    /// beginning of the 'typical' workday
+    pub start_of_business: NaiveTime,
+
+    /// longest allowed working time per day.
+    /// toml can not write values after the table of the rules, so it comes first
+    #[serde(default = "Breaks::default_maximum_working_seconds")]
+    pub maximum_working_seconds: u64,
+
+    /// breaks that are obligatory after working a certain time
+    pub rules: Vec<BreakRule>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct BreakRule {
+    /// the break is obligatory if one works longer than this
+    pub after_seconds: u64,
+    pub duration_seconds: u64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Vacation {
+    /// number of vacation days granted per year
+    pub days_per_year: f64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Saldo {
+    /// the saldo is cut down to this at the end of every year, leave blank to carry over everything
+    pub carry_over_cap_seconds: Option<u64>,
+
+    /// first day that counts, time entries before are ignored in the timesheet
+    pub tracking_start: Option<NaiveDate>,
+
+    /// the saldo on the tracking start, eg. taken over from the previous time recording
+    pub initial_saldo_seconds: Option<i64>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Export {
+    /// separator of the columns in csv exports
+    #[serde(default = "Export::default_delimiter")]
+    pub delimiter: char,
+    /// separator of the decimal hours, taken from the locale if not set
+    pub decimal_separator: Option<char>,
+}
+
+impl Export {
+    fn default_delimiter() -> char {
+        ','
+    }
+}
+
+impl Default for Export {
+    fn default() -> Self {
+        Export {
+            delimiter: Export::default_delimiter(),
+            decimal_separator: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Print {
+    /// name printed on the working time record
+    pub employee: Option<String>,
+    /// command converting the html record to pdf, {input} and {output} are replaced by the file paths
+    pub pdf_command: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Import {
+    /// separator of the columns in imported csv files
+    #[serde(default = "Import::default_delimiter")]
+    pub delimiter: char,
+    /// columns of the generic csv import
+    #[serde(default)]
+    pub columns: ColumnMapping,
+}
+
+impl Import {
+    fn default_delimiter() -> char {
+        ','
+    }
+}
+
+impl Default for Import {
+    fn default() -> Self {
+        Import {
+            delimiter: Import::default_delimiter(),
+            columns: ColumnMapping::default(),
+        }
+    }
+}
+
+/// Names of the columns of an imported csv file.
+/// If there is a start_time column, start only contains the date, the same goes for end.
+/// Without an end column the end is calculated from the duration.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ColumnMapping {
+    pub id: Option<String>,
+    pub start: String,
+    pub start_time: Option<String>,
+    pub end: Option<String>,
+    pub end_time: Option<String>,
+    pub duration: Option<String>,
+    pub description: Option<String>,
+    pub project: Option<String>,
+    /// comma separated list of tags
+    pub tags: Option<String>,
+    pub billable: Option<String>,
+}
+
+impl Default for ColumnMapping {
+    fn default() -> Self {
+        ColumnMapping {
+            id: None,
+            start: "Start".to_string(),
+            start_time: None,
+            end: Some("End".to_string()),
+            end_time: None,
+            duration: None,
+            description: Some("Description".to_string()),
+            project: Some("Project".to_string()),
+            tags: Some("Tags".to_string()),
+            billable: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Backup {
+    /// number of automatic backups kept before destructive operations
+    #[serde(default = "Backup::default_keep")]
+    pub keep: usize,
+}
+
+impl Backup {
+    fn default_keep() -> usize {
+        5
+    }
+}
+
+impl Default for Backup {
+    fn default() -> Self {
+        Backup {
+            keep: Backup::default_keep(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Daemon {
+    /// seconds between two detections in watch mode
+    #[serde(default = "Daemon::default_interval_seconds")]
+    pub interval_seconds: u64,
+}
+
+impl Daemon {
+    fn default_interval_seconds() -> u64 {
+        300
+    }
+}
+
+impl Default for Daemon {
+    fn default() -> Self {
+        Daemon {
+            interval_seconds: Daemon::default_interval_seconds(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Timeline {
+    /// detections of a location further apart than this are separate stays,
+    /// should be larger than the interval of the daemon
+    #[serde(default = "Timeline::default_max_gap_seconds")]
+    pub max_gap_seconds: u64,
+}
+
+impl Timeline {
+    fn default_max_gap_seconds() -> u64 {
+        900
+    }
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Timeline {
+            max_gap_seconds: Timeline::default_max_gap_seconds(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Retention {
+    /// raw detections older than this are compacted into intervals
+    #[serde(default = "Retention::default_raw_detection_days")]
+    pub raw_detection_days: u64,
+}
+
+impl Retention {
+    fn default_raw_detection_days() -> u64 {
+        30
+    }
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Retention {
+            raw_detection_days: Retention::default_raw_detection_days(),
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Probe {
     pub color: Option<Color>,
-    pub command: String,
+
+    /// the probe is stopped and counts as not detected after this, defaults to 10 seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_seconds: Option<u64>,
+
+    /// what is checked, eg. command = "..." or interface = "tun0".
+    /// Some kinds are tables and toml can not write values after them, so it comes last
+    #[serde(flatten)]
+    pub kind: ProbeKind,
+}
+
+/// Records the event name if the condition on the probes is true
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Rule {
+    pub name: String,
+    /// probe names combined with and, or, not, all(...), any(...) and parentheses
+    pub when: String,
+}
+
+/// The kinds of probes, every kind is configured with a key of its own
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ProbeKind {
+    /// a shell command which exits with 0 if detected
+    Command { command: String },
+    /// the connected wifi
+    Wifi { wifi: WifiMatch },
+    /// the default gateway
+    Gateway { gateway: GatewayMatch },
+    /// a network interface which is up, eg. the vpn tun0
+    Interface { interface: String },
+    /// a host:port which accepts tcp connections
+    Tcp { tcp: String },
+    /// a search domain in /etc/resolv.conf
+    DnsSearch { dns_search: String },
+    /// a connected usb device as vendor:product, eg. a docking station "17ef:3082"
+    Usb { usb: String },
+}
+
+/// Matches the connected wifi, every given value has to match
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+pub struct WifiMatch {
+    pub ssid: Option<String>,
+    pub bssid: Option<String>,
+}
+
+/// Matches the default gateway, every given value has to match
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+pub struct GatewayMatch {
+    pub ip: Option<Ipv4Addr>,
+    pub mac: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -46,6 +378,84 @@ pub enum Color {
     White,
 }
 
+impl WeeklySchedule {
+    pub fn expected_duration(&self, weekday: Weekday) -> Duration {
+        let seconds = match weekday {
+            Weekday::Mon => { self.monday }
+            Weekday::Tue => { self.tuesday }
+            Weekday::Wed => { self.wednesday }
+            Weekday::Thu => { self.thursday }
+            Weekday::Fri => { self.friday }
+            Weekday::Sat => { self.saturday }
+            Weekday::Sun => { self.sunday }
+        };
+        Duration::seconds(seconds.unwrap_or(0) as i64)
+    }
+}
+
+impl WorkWeek {
+    /// returns the schedule which is valid at the given date.
+    /// The period with the latest begin before the date wins over the default schedule.
+    pub fn schedule_at(&self, date: NaiveDate) -> Option<&WeeklySchedule> {
+        self.periods
+            .iter()
+            .filter(|period| period.from <= date)
+            .max_by_key(|period| period.from)
+            .map(|period| &period.schedule)
+            .or(self.schedule.as_ref())
+    }
+
+    /// returns the duration one is expected to work at the given date
+    pub fn expected_duration(&self, date: NaiveDate) -> Duration {
+        match self.schedule_at(date) {
+            Some(schedule) => {
+                schedule.expected_duration(date.weekday())
+            }
+            None => {
+                match date.weekday() {
+                    Weekday::Sat | Weekday::Sun => { Duration::zero() }
+                    _ => { Duration::seconds(self.default_expected_duration_seconds as i64) }
+                }
+            }
+        }
+    }
+}
+
+impl Breaks {
+    fn default_maximum_working_seconds() -> u64 {
+        10 * 3600
+    }
+
+    /// returns the longest break that is obligatory after working the given duration
+    pub fn required_break(&self, worked: Duration) -> Duration {
+        self.rules
+            .iter()
+            .filter(|rule| worked.num_seconds() > rule.after_seconds as i64)
+            .map(|rule| Duration::seconds(rule.duration_seconds as i64))
+            .max()
+            .unwrap_or(Duration::zero())
+    }
+
+    /// if started at the 'typical' time and done the obligatory breaks, at which time would we be done?
+    pub fn normalized_end_of_business(&self, worked: Duration) -> NaiveTime {
+        self.start_of_business + worked + self.required_break(worked)
+    }
+}
+
+impl Default for Breaks {
+    /// breaks as required by the german working hours act
+    fn default() -> Self {
+        Breaks {
+            start_of_business: NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            rules: vec![
+                BreakRule { after_seconds: 6 * 3600, duration_seconds: 30 * 60 },
+                BreakRule { after_seconds: 9 * 3600, duration_seconds: 45 * 60 },
+            ],
+            maximum_working_seconds: Breaks::default_maximum_working_seconds(),
+        }
+    }
+}
+
 impl From<Color> for cli_table::Color {
     fn from(value: Color) -> Self {
         match value {
@@ -112,7 +522,8 @@ impl ApplicationConfig {
     pub fn add_probe(&mut self, name: String, command: String) -> Result<(), ConfyError> {
         self.probes.insert(name, Probe {
             color: None,
-            command,
+            kind: ProbeKind::Command { command },
+            timeout_seconds: None,
         });
         self.save_config()
     }
@@ -126,6 +537,29 @@ impl ApplicationConfig {
         Some(ApplicationConfig::project_dirs()?.data_dir().join(format!("{}.db", env!("CARGO_PKG_NAME"))))
     }
 
+    /// directory of the automatic backups
+    pub fn backup_dir(&self) -> Option<PathBuf> {
+        Some(ApplicationConfig::project_dirs()?.data_dir().join("backups"))
+    }
+
+    /// the pid file which makes sure only one daemon is running
+    pub fn pid_file_path() -> Option<PathBuf> {
+        Some(ApplicationConfig::project_dirs()?.data_dir().join(format!("{}.pid", env!("CARGO_PKG_NAME"))))
+    }
+
+    /// where systemd looks for the units of the current user
+    pub fn systemd_unit_path() -> Option<PathBuf> {
+        Some(directories::BaseDirs::new()?
+            .config_dir()
+            .join("systemd/user")
+            .join(format!("{}.service", env!("CARGO_PKG_NAME"))))
+    }
+
+    /// a timesheet.html in the config directory replaces the bundled template of the working time record
+    pub fn template_path() -> Option<PathBuf> {
+        Some(ApplicationConfig::project_dirs()?.config_dir().join("timesheet.html"))
+    }
+
     fn config_file_path() -> Result<PathBuf, ConfyError> {
         let project_dirs = ApplicationConfig::project_dirs()
             .ok_or(ConfyError::BadConfigDirectory("could not determine home directory path".to_string()))?;
@@ -139,7 +573,11 @@ impl ApplicationConfig {
         let result: Result<ApplicationConfig, ConfyError> = confy::load_path(&path);
 
         match result {
-            Ok(config) => { Ok(config) }
+            Ok(config) => {
+                compile(&config.rules, &config.probes)
+                    .map_err(|error| anyhow::Error::msg(format!("Error in configuration {}:\n{}", path.display(), format!("{error}").red())))?;
+                Ok(config)
+            }
             Err(confy_error) => {
                 match confy_error {
                     ConfyError::BadTomlData(toml_error) => {
@@ -177,4 +615,106 @@ impl Default for ApplicationConfig {
         debug!("Created a new configuration from default.");
         toml::from_str(include_str!("default.conf")).expect("Default configuration not parseable!")
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::net::Ipv4Addr;
+    use chrono::{Duration, NaiveDate, NaiveTime};
+    use crate::config::{ApplicationConfig, Breaks, Probe, ProbeKind, SchedulePeriod, WeeklySchedule, WifiMatch, WorkWeek};
+
+    #[test]
+    fn test_expected_duration_from_schedule() {
+        let part_time = WeeklySchedule {
+            monday: Some(28800),
+            tuesday: Some(28800),
+            thursday: Some(28800),
+            friday: Some(14400),
+            ..Default::default()
+        };
+        let workweek = WorkWeek {
+            default_expected_duration_seconds: 27000,
+            schedule: None,
+            periods: vec![SchedulePeriod {
+                from: NaiveDate::from_ymd_opt(2023, 7, 1).unwrap(),
+                schedule: part_time,
+            }],
+        };
+
+        // friday and saturday before the period
+        assert_eq!(Duration::seconds(27000), workweek.expected_duration(NaiveDate::from_ymd_opt(2023, 6, 30).unwrap()));
+        assert_eq!(Duration::zero(), workweek.expected_duration(NaiveDate::from_ymd_opt(2023, 7, 1).unwrap()));
+
+        // wednesday, thursday and friday within the period
+        assert_eq!(Duration::zero(), workweek.expected_duration(NaiveDate::from_ymd_opt(2023, 7, 5).unwrap()));
+        assert_eq!(Duration::seconds(28800), workweek.expected_duration(NaiveDate::from_ymd_opt(2023, 7, 6).unwrap()));
+        assert_eq!(Duration::seconds(14400), workweek.expected_duration(NaiveDate::from_ymd_opt(2023, 7, 7).unwrap()));
+    }
+
+    #[test]
+    fn test_probe_kinds() {
+        let toml = r#"
+            [fritzbox]
+            color = "Green"
+            command = "ping 192.168.178.1 -c 1"
+
+            [vpn]
+            interface = "tun0"
+            timeout_seconds = 2
+
+            [office]
+            wifi = { ssid = "ACME" }
+
+            [home]
+            gateway = { ip = "192.168.178.1" }
+        "#;
+        let probes: HashMap<String, Probe> = toml::from_str(toml).unwrap();
+        assert_eq!(ProbeKind::Command { command: "ping 192.168.178.1 -c 1".to_string() }, probes["fritzbox"].kind);
+        assert_eq!(ProbeKind::Interface { interface: "tun0".to_string() }, probes["vpn"].kind);
+        assert_eq!(Some(2), probes["vpn"].timeout_seconds);
+        assert_eq!(ProbeKind::Wifi { wifi: WifiMatch { ssid: Some("ACME".to_string()), bssid: None } }, probes["office"].kind);
+        assert_eq!(Some(Ipv4Addr::new(192, 168, 178, 1)), match &probes["home"].kind {
+            ProbeKind::Gateway { gateway } => { gateway.ip }
+            _ => { None }
+        });
+
+        let serialized: HashMap<String, Probe> = toml::from_str(&toml::to_string(&probes).unwrap()).unwrap();
+        assert_eq!(probes["office"].kind, serialized["office"].kind);
+        assert!(toml::from_str::<Probe>("unknown = 1").is_err());
+    }
+
+    #[test]
+    fn test_save_default_config() {
+        let toml = toml::to_string(&ApplicationConfig::default()).unwrap();
+        let config: ApplicationConfig = toml::from_str(&toml).unwrap();
+        assert_eq!(36000, config.breaks.maximum_working_seconds);
+    }
+
+    #[test]
+    fn test_save_config_with_native_probe() {
+        let mut config = ApplicationConfig::default();
+        config.probes.insert("office".to_string(), Probe {
+            color: None,
+            timeout_seconds: Some(2),
+            kind: ProbeKind::Wifi { wifi: WifiMatch { ssid: Some("ACME".to_string()), bssid: None } },
+        });
+
+        // confy writes with its own version of toml
+        let path = std::env::temp_dir().join(format!("papierkram-test-config-{}.toml", std::process::id()));
+        confy::store_path(&path, &config).unwrap();
+        let loaded: ApplicationConfig = confy::load_path(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(Some(2), loaded.probes["office"].timeout_seconds);
+        assert_eq!(config.probes["office"].kind, loaded.probes["office"].kind);
+    }
+
+    #[test]
+    fn test_break_rules() {
+        let breaks = Breaks::default();
+        assert_eq!(Duration::zero(), breaks.required_break(Duration::hours(6)));
+        assert_eq!(Duration::minutes(30), breaks.required_break(Duration::minutes(361)));
+        assert_eq!(Duration::minutes(45), breaks.required_break(Duration::minutes(541)));
+        assert_eq!(NaiveTime::from_hms_opt(16, 30, 0).unwrap(), breaks.normalized_end_of_business(Duration::hours(8)));
+    }
+}
@@ -0,0 +1,154 @@
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use log::{debug, warn};
+use signal_hook::consts::{SIGINT, SIGTERM};
+
+/// how often a sleeping daemon checks if it has to stop
+const SLEEP_STEP: Duration = Duration::from_millis(250);
+
+#[derive(thiserror::Error, Debug)]
+pub enum DaemonError {
+    #[error("Another papierkram daemon is already running with pid {0}")]
+    AlreadyRunning(u32),
+
+    #[error("Could not write pid file {0}")]
+    PidFile(String, #[source] io::Error),
+
+    #[error("Could not register the signal handlers")]
+    Signal(#[source] io::Error),
+}
+
+/// A pid file which is locked as long as it exists.
+/// It is removed when dropped.
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Creates the pid file for the current process.
+    /// A pid file left over from a process which is not running anymore is replaced.
+    pub fn acquire(path: &Path) -> Result<PidFile, DaemonError> {
+        PidFile::acquire_for(path, std::process::id())
+    }
+
+    fn acquire_for(path: &Path, pid: u32) -> Result<PidFile, DaemonError> {
+        let error = |error| DaemonError::PidFile(path.display().to_string(), error);
+        if let Some(running) = read_pid(path).filter(|pid| is_running(*pid)) {
+            return Err(DaemonError::AlreadyRunning(running));
+        }
+        if path.exists() {
+            warn!("Removing stale pid file {}", path.display());
+            fs::remove_file(path).map_err(error)?;
+        }
+
+        // create_new fails if another daemon created the file in the meantime
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map_err(|_| DaemonError::AlreadyRunning(read_pid(path).unwrap_or_default()))?;
+        writeln!(file, "{pid}").map_err(error)?;
+        debug!("Wrote pid {pid} to {}", path.display());
+        Ok(PidFile { path: path.to_path_buf() })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(error) = fs::remove_file(&self.path) {
+            warn!("Could not remove pid file {}: {error}", self.path.display());
+        }
+    }
+}
+
+/// returns the pid of the running daemon which holds the pid file
+pub fn running_daemon(path: &Path) -> Option<u32> {
+    read_pid(path).filter(|pid| *pid != std::process::id() && is_running(*pid))
+}
+
+fn read_pid(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+fn is_running(pid: u32) -> bool {
+    Path::new(&format!("/proc/{pid}")).exists()
+}
+
+/// Returns a flag which is set as soon as the process receives SIGTERM or SIGINT
+pub fn stop_flag() -> Result<Arc<AtomicBool>, DaemonError> {
+    let stop = Arc::new(AtomicBool::new(false));
+    for signal in [SIGTERM, SIGINT] {
+        signal_hook::flag::register(signal, Arc::clone(&stop)).map_err(DaemonError::Signal)?;
+    }
+    Ok(stop)
+}
+
+/// Sleeps for the given duration unless the stop flag is set.
+/// Returns false if it was interrupted.
+pub fn sleep_unless_stopped(duration: Duration, stop: &AtomicBool) -> bool {
+    let deadline = Instant::now() + duration;
+    while Instant::now() < deadline {
+        if stop.load(Ordering::Relaxed) {
+            return false;
+        }
+        std::thread::sleep(SLEEP_STEP.min(deadline - Instant::now()));
+    }
+    !stop.load(Ordering::Relaxed)
+}
+
+/// A systemd user unit which runs the daemon with the given executable
+pub fn systemd_unit(executable: &Path) -> String {
+    format!(
+        "[Unit]\n\
+         Description=papierkram workplace detection\n\
+         After=network-online.target\n\
+         \n\
+         [Service]\n\
+         Type=simple\n\
+         ExecStart={} daemon run\n\
+         Restart=on-failure\n\
+         \n\
+         [Install]\n\
+         WantedBy=default.target\n",
+        executable.display()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use crate::daemon::{DaemonError, PidFile, running_daemon, systemd_unit};
+
+    #[test]
+    fn test_pid_file() {
+        let path = std::env::temp_dir().join(format!("papierkram-test-{}.pid", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let pid_file = PidFile::acquire(&path).unwrap();
+        assert_eq!(format!("{}\n", std::process::id()), fs::read_to_string(&path).unwrap());
+        assert!(matches!(PidFile::acquire(&path), Err(DaemonError::AlreadyRunning(_))));
+        // the own process is not reported as running daemon
+        assert_eq!(None, running_daemon(&path));
+        drop(pid_file);
+        assert!(!path.exists());
+
+        // pid files of dead processes are replaced, u32::MAX is never a valid pid
+        fs::write(&path, format!("{}\n", u32::MAX)).unwrap();
+        assert_eq!(None, running_daemon(&path));
+        let pid_file = PidFile::acquire_for(&path, 1).unwrap();
+        assert_eq!("1\n", fs::read_to_string(&path).unwrap());
+        assert_eq!(Some(1), running_daemon(&path));
+        drop(pid_file);
+
+        assert!(systemd_unit(Path::new("/usr/bin/papierkram")).contains("ExecStart=/usr/bin/papierkram daemon run\n"));
+    }
+}
@@ -1,30 +1,77 @@
 use thiserror::Error;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 
 
-use chrono::{Duration, NaiveDate, Utc};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
 use log::debug;
-use rusqlite::{Connection, Params, params, Row};
+use rusqlite::{Connection, OptionalExtension, Params, params, Row};
+use rusqlite::types::Type;
 
-use crate::config::ApplicationConfig;
-use crate::datastore::DataStoreError::FileSystem;
+use crate::config::{ApplicationConfig, WorkWeek};
+use crate::datastore::DataStoreError::{FileSystem, SchemaTooNew};
 
 
-use crate::models::{Event, ExpectedDuration, TimeEntry, TimeSheet};
+use crate::models::{Absence, Client, Event, ExpectedDuration, Holiday, PresenceInterval, Project, SaldoAdjustment, Tag, TimeEntry, TimeEntryDetails, TimeSheet, TimeSheetRow, Workspace};
 
 type Result<T> = anyhow::Result<T, anyhow::Error>;
 
+/// All schema migrations in the order they have to be applied.
+/// The schema version of a database is the number of migrations already applied to it,
+/// so migrations must never be changed or reordered once they are released.
+static MIGRATIONS: &[&str] = &[
+    include_str!("sql/migrations/0001_initial.sql"),
+    include_str!("sql/migrations/0002_expected_duration_source.sql"),
+    include_str!("sql/migrations/0003_holidays.sql"),
+    include_str!("sql/migrations/0004_absences.sql"),
+    include_str!("sql/migrations/0005_configurable_breaks.sql"),
+    include_str!("sql/migrations/0006_actual_business_hours.sql"),
+    include_str!("sql/migrations/0007_settings.sql"),
+    include_str!("sql/migrations/0008_toggl_metadata.sql"),
+    include_str!("sql/migrations/0009_time_entry_source.sql"),
+    include_str!("sql/migrations/0010_saldo_adjustments.sql"),
+    include_str!("sql/migrations/0011_tracking_start.sql"),
+    include_str!("sql/migrations/0012_presence_intervals.sql"),
+    include_str!("sql/migrations/0013_location_intervals.sql"),
+    include_str!("sql/migrations/0014_presence_without_overlaps.sql"),
+    include_str!("sql/migrations/0015_absences_by_kind.sql"),
+    include_str!("sql/migrations/0016_presence_after_compaction.sql"),
+];
+
+/// key of the setting the timesheet view reads the tracking start from
+const TRACKING_START_KEY: &str = "saldo.tracking_start";
+
+/// key of the setting the presence_intervals view reads the maximum gap between two detections from
+const MAX_GAP_KEY: &str = "timeline.max_gap_seconds";
+
 #[derive(Error, Debug)]
 enum DataStoreError {
     #[error("Cannot acces database file: {0}")]
-    FileSystem(String)
+    FileSystem(String),
+
+    #[error("The database has schema version {0} but this version of papierkram only supports up to {1}. Please upgrade papierkram.")]
+    SchemaTooNew(usize, usize),
 }
 
 pub trait DataStore {
     fn connect_database(config: &ApplicationConfig) -> Result<Connection>;
 
+    /// applies all migrations the database has not seen yet.
+    /// Every migration runs in its own transaction together with the update of the schema version.
     fn run_migrations(&mut self) -> Result<()>;
 
+    /// returns the number of migrations applied to this database
+    fn schema_version(&mut self) -> Result<usize>;
+
+    /// writes a consistent copy of the whole database including its schema version to a new file
+    fn backup_into(&mut self, path: &Path) -> Result<()>;
+
+    /// returns true if sqlite finds no corruption in the database
+    fn check_integrity(&mut self) -> Result<bool>;
+
+    /// rebuilds the database file to give the space of deleted rows back to the file system
+    fn vacuum(&mut self) -> Result<()>;
+
     /// Executes a select statement and converts all Rows into T using the function from_row
     fn view_query<T, F, P>(&mut self, sql: &str, params: P, fro_row: F) -> Result<Vec<T>>
         where
@@ -32,43 +79,135 @@ pub trait DataStore {
             P: Params;
 
     /// Inserts a Vector of Documents into a table using the sql statement and the function to_row
-    fn insert_query<'a, T, F, P>(&mut self, sql: &str, docs: &'a Vec<T>, to_row: F) -> Result<()>
+    fn insert_query<'a, T, F, P>(&mut self, sql: &str, docs: &'a [T], to_row: F) -> Result<()>
         where
             F: Fn(&'a T) -> P,
             P: Params;
 
 
     fn insert_event(&mut self, event: &Event) -> Result<()>;
-    fn insert_current_event(&mut self, name: &String) -> Result<()>;
-    fn insert_events(&mut self, events: &Vec<Event>) -> Result<()>;
+    fn insert_current_event(&mut self, name: &str) -> Result<()>;
+    fn insert_events(&mut self, events: &[Event]) -> Result<()>;
     fn insert_time_entry(&mut self, time_entry: &TimeEntry) -> Result<()>;
-    fn insert_time_entries(&mut self, time_entry: &Vec<TimeEntry>) -> Result<()>;
+
+    /// returns a new id for a locally created time entry.
+    /// Local ids are negative, so they can never collide with the ids from toggl.
+    fn next_local_time_entry_id(&mut self) -> Result<i64>;
+    fn insert_time_entries(&mut self, time_entry: &[TimeEntry]) -> Result<()>;
     fn insert_expected_duration(&mut self, expected_duration: ExpectedDuration) -> Result<()>;
 
-    /// inserts the default expected duration for every date where a time entry exists
-    /// but no expected duration ist given.
-    fn insert_default_expected_duration(&mut self, default: Duration) -> Result<()>;
+    /// removes all expected durations between start and end which do not originate
+    /// from the schedule and returns how many were removed
+    fn remove_expected_duration_overrides(&mut self, start: NaiveDate, end: NaiveDate) -> Result<usize>;
+
+    /// removes the time entries with the given ids and returns how many were removed
+    fn remove_time_entries(&mut self, ids: &[i64]) -> Result<usize>;
+
+    /// removes all toggl time entries starting between start and end (exclusive)
+    /// which are not in keep and returns how many were removed
+    fn remove_toggl_time_entries_except(&mut self, start: NaiveDate, end: NaiveDate, keep: &[i64]) -> Result<usize>;
+
+    /// returns the ids of all time entries imported from the source by their external id
+    fn list_imported_time_entry_ids(&mut self, source: &str) -> Result<HashMap<String, i64>>;
+
+    /// inserts or replaces time entries together with their source and external id.
+    /// Fails as a whole if one of the time entries can not be inserted.
+    fn insert_imported_time_entries(&mut self, source: &str, time_entries: &[(String, TimeEntry)]) -> Result<()>;
+
+    /// returns the id of the project with the given name.
+    /// Unknown projects are created locally with a negative id.
+    fn find_or_insert_local_project(&mut self, name: &str) -> Result<i64>;
+
+    /// inserts a saldo adjustment and returns its id
+    fn insert_saldo_adjustment(&mut self, adjustment: &SaldoAdjustment) -> Result<i64>;
+
+    /// removes the manual saldo adjustment with the given id and returns how many were removed
+    fn remove_saldo_adjustment(&mut self, id: i64) -> Result<usize>;
+
+    /// lists all saldo adjustments between start and end sorted by date ASC
+    fn list_saldo_adjustments(&mut self, start: NaiveDate, end: NaiveDate) -> Result<Vec<SaldoAdjustment>>;
+
+    /// returns the saldo at the end of the given date
+    fn view_saldo(&mut self, date: NaiveDate) -> Result<Duration>;
+
+    /// recalculates the adjustments which cut the saldo down to the cap at the end of every year before until.
+    /// Without a cap all of them are removed.
+    fn update_carry_over_cap(&mut self, cap: Option<Duration>, until: NaiveDate) -> Result<()>;
+
+    fn update_setting(&mut self, key: &str, value: &str) -> Result<()>;
+    fn view_setting(&mut self, key: &str) -> Result<Option<String>>;
+
+    fn insert_workspaces(&mut self, workspaces: &[Workspace]) -> Result<()>;
+    fn insert_projects(&mut self, projects: &[Project]) -> Result<()>;
+    fn insert_clients(&mut self, clients: &[Client]) -> Result<()>;
+    fn insert_tags(&mut self, tags: &[Tag]) -> Result<()>;
+
+    fn insert_absences(&mut self, absences: &[Absence]) -> Result<()>;
+
+    /// removes all absences between start and end and returns how many were removed
+    fn remove_absences(&mut self, start: NaiveDate, end: NaiveDate) -> Result<usize>;
+
+    /// replaces all known holidays
+    fn replace_holidays(&mut self, holidays: &[Holiday]) -> Result<()>;
+
+    /// inserts the expected duration from the work week schedule for every date
+    /// from the tracking start until the given date. Holidays are expected to be free.
+    /// Expected durations which do not originate from the schedule are kept.
+    fn insert_scheduled_expected_durations(&mut self, workweek: &WorkWeek, until: NaiveDate) -> Result<()>;
 
     /// lists all events sorted by date ASC
     fn list_events(&mut self) -> Result<Vec<Event>>;
-    /// lists all time entries sorted by date ASC
-    fn list_time_entries(&mut self) -> Result<Vec<TimeEntry>>;
 
-    /// lists all expected durations sorted by date ASC
-    fn list_expected_durations(&mut self) -> Result<Vec<ExpectedDuration>>;
+    /// returns the latest time entry which is not stopped yet
+    fn view_running_time_entry(&mut self) -> Result<Option<TimeEntry>>;
 
-    /// returns a map of all expected durations per date
-    fn view_expected_durations_by_date(&mut self) -> Result<HashMap<NaiveDate, Duration>>;
+    /// lists all time entries starting between start and end with the names of their workspace,
+    /// project and client sorted by date ASC
+    fn list_time_entry_details(&mut self, start: NaiveDate, end: NaiveDate) -> Result<Vec<TimeEntryDetails>>;
 
-    /// returns a map of all events grouped by their dates
-    fn view_event_by_date(&mut self) -> Result<HashMap<NaiveDate, Vec<Event>>>;
+    /// checks if there are time entries whose workspace or project is unknown
+    fn view_missing_toggl_metadata(&mut self) -> Result<bool>;
 
-    fn view_events_where_date_eq(&mut self, date: NaiveDate) -> Result<Vec<Event>>;
+    /// lists all expected durations between start and end sorted by date ASC
+    fn list_expected_durations(&mut self, start: NaiveDate, end: NaiveDate) -> Result<Vec<ExpectedDuration>>;
 
-    // view all known event names
-    fn view_event_names(&mut self) -> Result<Vec<String>>;
+    /// lists all holidays between start and end sorted by date ASC
+    fn list_holidays(&mut self, start: NaiveDate, end: NaiveDate) -> Result<Vec<Holiday>>;
 
-    /// returns the timesheet with all necessary information
+    /// lists all absences between start and end sorted by date ASC
+    fn list_absences(&mut self, start: NaiveDate, end: NaiveDate) -> Result<Vec<Absence>>;
+
+    /// returns the date of the first time entry
+    fn view_first_date(&mut self) -> Result<Option<NaiveDate>>;
+
+    /// returns the first day the timesheet counts, which is the tracking start if one is set
+    /// and the date of the first time entry otherwise
+    fn view_tracking_start(&mut self) -> Result<Option<NaiveDate>>;
+
+    /// stores the tracking start for the timesheet and replaces the initial saldo on that day.
+    /// Without a tracking start the initial saldo is booked on the date of the first time entry.
+    fn update_tracking_start(&mut self, start: Option<NaiveDate>, initial_saldo: Duration) -> Result<()>;
+
+    /// lists the presence intervals starting between start and end sorted by their start
+    fn view_presence_intervals(&mut self, start: NaiveDate, end: NaiveDate) -> Result<Vec<PresenceInterval>>;
+
+    /// lists all presence intervals including the compacted ones sorted by their start
+    fn list_presence_intervals(&mut self) -> Result<Vec<PresenceInterval>>;
+
+    /// saves the intervals like compacted detections, eg. when they are imported from an export.
+    /// Detections and intervals covered by an interval are replaced by it
+    fn insert_location_intervals(&mut self, intervals: &[PresenceInterval]) -> Result<()>;
+
+    /// sets the longest gap between two detections that still belongs to the same presence interval
+    fn update_max_gap(&mut self, max_gap: Duration) -> Result<()>;
+
+    /// replaces the raw detections of all presence intervals that ended before the given instant
+    /// by one row in location_intervals per interval, which leaves the presence intervals unchanged.
+    /// Returns the number of removed detections and of written intervals
+    fn compact_detections(&mut self, before: DateTime<Utc>) -> Result<(usize, usize)>;
+
+    /// returns the timesheet with all necessary information.
+    /// The normalized start and end of business and the warnings are left empty, see TimeSheetRow::apply_breaks
     fn view_timesheet(&mut self, start: NaiveDate, end: NaiveDate) -> Result<TimeSheet>;
 
     /// returns the timesheet with all necessary information
@@ -101,11 +240,46 @@ impl DataStore for Connection {
     }
 
     fn run_migrations(&mut self) -> Result<()> {
-        // running migrations
-        let sql = include_str!("sql/create_tables.sql");
-        debug!("Executing sql: {sql}");
+        let current_version = self.schema_version()?;
+        let supported_version = MIGRATIONS.len();
+
+        if current_version > supported_version {
+            return Err(SchemaTooNew(current_version, supported_version).into());
+        }
+
+        for (version, sql) in MIGRATIONS.iter().enumerate().skip(current_version) {
+            let version = version + 1;
+            debug!("Migrating database to schema version {version}: {sql}");
+
+            let tx = self.transaction()?;
+            tx.execute_batch(sql)?;
+            tx.pragma_update(None, "user_version", version)?;
+            tx.commit()?;
+        }
+
+        Ok(())
+    }
+
+    fn schema_version(&mut self) -> Result<usize> {
+        Ok(self.pragma_query_value(None, "user_version", |row| row.get(0))?)
+    }
+
+    fn backup_into(&mut self, path: &Path) -> Result<()> {
+        let path = path
+            .to_str()
+            .ok_or(FileSystem(format!("Invalid backup path: {}", path.display())))?;
+        self.execute("VACUUM INTO ?;", params![path])?;
+        Ok(())
+    }
+
+    fn check_integrity(&mut self) -> Result<bool> {
+        let result: String = self.pragma_query_value(None, "quick_check", |row| row.get(0))?;
+        Ok(result == "ok")
+    }
 
-        Ok(self.execute_batch(sql)?)
+    fn vacuum(&mut self) -> Result<()> {
+        self.execute_batch("VACUUM;")?;
+        Ok(())
     }
 
 
@@ -126,7 +300,7 @@ impl DataStore for Connection {
         Ok(result)
     }
 
-    fn insert_query<'a, T, F, P>(&mut self, sql: &str, docs: &'a Vec<T>, to_row: F) -> Result<()>
+    fn insert_query<'a, T, F, P>(&mut self, sql: &str, docs: &'a [T], to_row: F) -> Result<()>
         where
             F: Fn(&'a T) -> P,
             P: Params
@@ -147,17 +321,17 @@ impl DataStore for Connection {
     }
 
     fn insert_event(&mut self, event: &Event) -> Result<()> {
-        self.insert_events(&vec![event.clone()])
+        self.insert_events(std::slice::from_ref(event))
     }
 
-    fn insert_current_event(&mut self, name: &String) -> Result<()> {
+    fn insert_current_event(&mut self, name: &str) -> Result<()> {
         self.insert_event(&Event {
             time: Utc::now(),
-            name: name.clone(),
+            name: name.to_string(),
         })
     }
 
-    fn insert_events(&mut self, events: &Vec<Event>) -> Result<()> {
+    fn insert_events(&mut self, events: &[Event]) -> Result<()> {
         self.insert_query(
             "REPLACE INTO office_location (instant, location) VALUES (?, ?);",
             events,
@@ -169,12 +343,20 @@ impl DataStore for Connection {
     }
 
     fn insert_time_entry(&mut self, time_entry: &TimeEntry) -> Result<()> {
-        self.insert_time_entries(&vec![time_entry.clone()])
+        self.insert_time_entries(std::slice::from_ref(time_entry))
     }
 
-    fn insert_time_entries(&mut self, time_entries: &Vec<TimeEntry>) -> Result<()> {
+    fn next_local_time_entry_id(&mut self) -> Result<i64> {
+        Ok(self.query_row(
+            "SELECT MIN(0, IFNULL(MIN(id), 0)) - 1 FROM time_entries;",
+            params![],
+            |row| row.get(0),
+        )?)
+    }
+
+    fn insert_time_entries(&mut self, time_entries: &[TimeEntry]) -> Result<()> {
         self.insert_query(
-            "REPLACE INTO time_entries (id, description, start, stop, project_id, workspace_id) VALUES (?, ?, ?, ?, ?, ?);",
+            "REPLACE INTO time_entries (id, description, start, stop, project_id, workspace_id, billable, tags) VALUES (?, ?, ?, ?, ?, ?, ?, ?);",
             time_entries,
             |time_entry| (
                 time_entry.id,
@@ -182,27 +364,302 @@ impl DataStore for Connection {
                 time_entry.start,
                 time_entry.stop,
                 time_entry.project_id,
-                time_entry.workspace_id
+                time_entry.workspace_id,
+                time_entry.billable,
+                serde_json::to_string(&time_entry.tags).unwrap_or("[]".to_string()),
             ),
         )
     }
 
     fn insert_expected_duration(&mut self, expected_duration: ExpectedDuration) -> Result<()> {
         self.insert_query(
-            "REPLACE INTO expected_duration (date, duration) VALUES (?, ?);",
-            &vec![expected_duration],
+            "REPLACE INTO expected_duration (date, duration, source) VALUES (?, ?, ?);",
+            &[expected_duration],
             |expected_duration| (
                 expected_duration.date,
                 expected_duration.duration.clone(),
+                expected_duration.source.clone(),
             ),
         )
     }
 
-    fn insert_default_expected_duration(&mut self, default: Duration) -> Result<()> {
+    fn remove_expected_duration_overrides(&mut self, start: NaiveDate, end: NaiveDate) -> Result<usize> {
+        Ok(self.execute(
+            "DELETE FROM expected_duration WHERE DATE(date) BETWEEN ? AND ? AND source != 'schedule';",
+            params![start, end],
+        )?)
+    }
+
+    fn remove_time_entries(&mut self, ids: &[i64]) -> Result<usize> {
+        let tx = self.transaction()?;
+        let mut removed = 0;
+        {
+            let mut stmt = tx.prepare("DELETE FROM time_entries WHERE id = ?;")?;
+            for id in ids {
+                removed += stmt.execute(params![id])?;
+            }
+        }
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    fn remove_toggl_time_entries_except(&mut self, start: NaiveDate, end: NaiveDate, keep: &[i64]) -> Result<usize> {
+        let ids: Vec<i64> = self.view_query(
+            "SELECT id FROM time_entries WHERE id > 0 AND DATE(start) >= ? AND DATE(start) < ?;",
+            params![start, end],
+            |row| row.get("id"),
+        )?;
+        let removed: Vec<i64> = ids
+            .into_iter()
+            .filter(|id| !keep.contains(id))
+            .collect();
+        self.remove_time_entries(&removed)
+    }
+
+    fn list_imported_time_entry_ids(&mut self, source: &str) -> Result<HashMap<String, i64>> {
+        Ok(self.view_query(
+            "SELECT external_id, id FROM time_entries WHERE source = ?;",
+            params![source],
+            |row| Ok((row.get("external_id")?, row.get("id")?)),
+        )?.into_iter().collect())
+    }
+
+    fn insert_imported_time_entries(&mut self, source: &str, time_entries: &[(String, TimeEntry)]) -> Result<()> {
+        let tx = self.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "REPLACE INTO time_entries (id, description, start, stop, project_id, workspace_id, billable, tags, source, external_id) \
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?);"
+            )?;
+            for (external_id, time_entry) in time_entries {
+                stmt.execute(params![
+                    time_entry.id,
+                    time_entry.description,
+                    time_entry.start,
+                    time_entry.stop,
+                    time_entry.project_id,
+                    time_entry.workspace_id,
+                    time_entry.billable,
+                    serde_json::to_string(&time_entry.tags)?,
+                    source,
+                    external_id,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn find_or_insert_local_project(&mut self, name: &str) -> Result<i64> {
+        let ids: Vec<i64> = self.view_query(
+            "SELECT id FROM projects WHERE name = ? ORDER BY id DESC LIMIT 1;",
+            params![name],
+            |row| row.get("id"),
+        )?;
+        if let Some(id) = ids.first() {
+            return Ok(*id);
+        }
+
+        let id: i64 = self.query_row(
+            "SELECT MIN(0, IFNULL(MIN(id), 0)) - 1 FROM projects;",
+            params![],
+            |row| row.get(0),
+        )?;
+        self.execute(
+            "INSERT INTO projects (id, workspace_id, name) VALUES (?, 0, ?);",
+            params![id, name],
+        )?;
+        Ok(id)
+    }
+
+    fn insert_saldo_adjustment(&mut self, adjustment: &SaldoAdjustment) -> Result<i64> {
+        self.execute(
+            "INSERT INTO saldo_adjustments (date, amount, reason, source) VALUES (?, ?, ?, ?);",
+            params![adjustment.date, adjustment.amount, adjustment.reason, adjustment.source],
+        )?;
+        Ok(self.last_insert_rowid())
+    }
+
+    fn remove_saldo_adjustment(&mut self, id: i64) -> Result<usize> {
+        Ok(self.execute(
+            "DELETE FROM saldo_adjustments WHERE id = ? AND source = 'manual';",
+            params![id],
+        )?)
+    }
+
+    fn list_saldo_adjustments(&mut self, start: NaiveDate, end: NaiveDate) -> Result<Vec<SaldoAdjustment>> {
+        self.view_query(
+            "SELECT id, DATE(date) AS date, amount, reason, source FROM saldo_adjustments \
+             WHERE DATE(date) BETWEEN ? AND ? ORDER BY date, id;",
+            params![start, end],
+            |row| Ok(SaldoAdjustment {
+                id: row.get("id")?,
+                date: row.get("date")?,
+                amount: row.get("amount")?,
+                reason: row.get("reason")?,
+                source: row.get("source")?,
+            }),
+        )
+    }
+
+    fn view_saldo(&mut self, date: NaiveDate) -> Result<Duration> {
+        let saldo: Option<i64> = self.query_row(
+            "SELECT saldo FROM timesheet WHERE date <= ? ORDER BY date DESC LIMIT 1;",
+            params![date],
+            |row| row.get(0),
+        ).optional()?;
+        Ok(Duration::seconds(saldo.unwrap_or_default()))
+    }
+
+    fn update_carry_over_cap(&mut self, cap: Option<Duration>, until: NaiveDate) -> Result<()> {
+        self.execute("DELETE FROM saldo_adjustments WHERE source = 'cap';", params![])?;
+        let (cap, first_date) = match (cap, self.view_tracking_start()?) {
+            (Some(cap), Some(first_date)) => { (cap, first_date) }
+            _ => { return Ok(()); }
+        };
+
+        // every cut changes the saldo of the following years, so they are calculated one after another
+        for year in first_date.year()..until.year() {
+            let saldo = self.view_saldo(NaiveDate::from_ymd_opt(year, 12, 31).unwrap())?;
+            if saldo > cap {
+                self.insert_saldo_adjustment(&SaldoAdjustment {
+                    id: 0,
+                    date: NaiveDate::from_ymd_opt(year + 1, 1, 1).unwrap(),
+                    amount: crate::duration_newtype::Duration::of(cap - saldo),
+                    reason: format!("carry-over cap {year}"),
+                    source: "cap".to_string(),
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn update_setting(&mut self, key: &str, value: &str) -> Result<()> {
+        self.execute(
+            "REPLACE INTO settings (key, value) VALUES (?, ?);",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    fn view_setting(&mut self, key: &str) -> Result<Option<String>> {
+        Ok(self.view_query(
+            "SELECT value FROM settings WHERE key = ?;",
+            params![key],
+            |row| row.get("value"),
+        )?.pop())
+    }
+
+    fn insert_workspaces(&mut self, workspaces: &[Workspace]) -> Result<()> {
+        self.insert_query(
+            "REPLACE INTO workspaces (id, name) VALUES (?, ?);",
+            workspaces,
+            |workspace| (
+                workspace.id,
+                workspace.name.clone(),
+            ),
+        )
+    }
+
+    fn insert_projects(&mut self, projects: &[Project]) -> Result<()> {
+        self.insert_query(
+            "REPLACE INTO projects (id, workspace_id, client_id, name, color, active) VALUES (?, ?, ?, ?, ?, ?);",
+            projects,
+            |project| (
+                project.id,
+                project.workspace_id,
+                project.client_id,
+                project.name.clone(),
+                project.color.clone(),
+                project.active,
+            ),
+        )
+    }
+
+    fn insert_clients(&mut self, clients: &[Client]) -> Result<()> {
+        self.insert_query(
+            "REPLACE INTO clients (id, workspace_id, name) VALUES (?, ?, ?);",
+            clients,
+            |client| (
+                client.id,
+                client.workspace_id,
+                client.name.clone(),
+            ),
+        )
+    }
+
+    fn insert_tags(&mut self, tags: &[Tag]) -> Result<()> {
+        self.insert_query(
+            "REPLACE INTO tags (id, workspace_id, name) VALUES (?, ?, ?);",
+            tags,
+            |tag| (
+                tag.id,
+                tag.workspace_id,
+                tag.name.clone(),
+            ),
+        )
+    }
+
+    fn insert_absences(&mut self, absences: &[Absence]) -> Result<()> {
         self.insert_query(
-            "INSERT OR IGNORE into expected_duration select DATE(start) as date, ? as duration FROM time_entries GROUP BY DATE(start);",
-            &vec![default],
-            |duration| (
+            "REPLACE INTO absences (date, kind, fraction) VALUES (?, ?, ?);",
+            absences,
+            |absence| (
+                absence.date,
+                absence.kind,
+                absence.fraction,
+            ),
+        )
+    }
+
+    fn remove_absences(&mut self, start: NaiveDate, end: NaiveDate) -> Result<usize> {
+        Ok(self.execute(
+            "DELETE FROM absences WHERE DATE(date) BETWEEN ? AND ?;",
+            params![start, end],
+        )?)
+    }
+
+    fn replace_holidays(&mut self, holidays: &[Holiday]) -> Result<()> {
+        let tx = self.transaction()?;
+        tx.execute("DELETE FROM holidays;", params![])?;
+        {
+            let mut stmt = tx.prepare("REPLACE INTO holidays (date, name) VALUES (?, ?);")?;
+            for holiday in holidays {
+                stmt.execute(params![holiday.date, holiday.name])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn insert_scheduled_expected_durations(&mut self, workweek: &WorkWeek, until: NaiveDate) -> Result<()> {
+        let expected_durations: Vec<(NaiveDate, Duration)> = match self.view_tracking_start()? {
+            None => { vec![] }
+            Some(first_date) => {
+                let holidays: HashSet<NaiveDate> = self.list_holidays(first_date, until)?
+                    .iter()
+                    .map(|holiday| holiday.date)
+                    .collect();
+
+                first_date
+                    .iter_days()
+                    .take_while(|date| *date <= until)
+                    .map(|date| if holidays.contains(&date) {
+                        (date, Duration::zero())
+                    } else {
+                        (date, workweek.expected_duration(date))
+                    })
+                    .collect()
+            }
+        };
+        debug!("Inserting {} expected durations from the schedule.", expected_durations.len());
+
+        self.insert_query(
+            "INSERT INTO expected_duration (date, duration, source) VALUES (?, ?, 'schedule') \
+             ON CONFLICT(date) DO UPDATE SET duration = excluded.duration WHERE source = 'schedule';",
+            &expected_durations,
+            |(date, duration)| (
+                date,
                 duration.num_seconds(),
             ),
         )
@@ -219,99 +676,214 @@ impl DataStore for Connection {
         )
     }
 
-    fn list_time_entries(&mut self) -> Result<Vec<TimeEntry>> {
-        self.view_query(
-            "SELECT id, description, start, stop, project_id, workspace_id from time_entries;",
+    fn view_running_time_entry(&mut self) -> Result<Option<TimeEntry>> {
+        Ok(self.view_query(
+            &format!("SELECT {TIME_ENTRY_COLUMNS} from time_entries WHERE stop IS NULL ORDER BY start DESC LIMIT 1;"),
             params![],
-            |row| Ok(crate::models::TimeEntry {
-                id: row.get("id")?,
-                description: row.get("description")?,
-                start: row.get("start")?,
-                stop: row.get("stop")?,
-                project_id: row.get("project_id")?,
-                workspace_id: row.get("workspace_id")?,
+            time_entry_row,
+        )?.pop())
+    }
+
+    fn list_time_entry_details(&mut self, start: NaiveDate, end: NaiveDate) -> Result<Vec<TimeEntryDetails>> {
+        self.view_query(
+            &format!("SELECT {TIME_ENTRY_COLUMNS}, workspace, project, client FROM time_entry_details WHERE DATE(start) BETWEEN ? AND ?;"),
+            params![start, end],
+            |row| Ok(TimeEntryDetails {
+                time_entry: time_entry_row(row)?,
+                workspace: row.get("workspace")?,
+                project: row.get("project")?,
+                client: row.get("client")?,
             }),
         )
     }
 
-    fn list_expected_durations(&mut self) -> Result<Vec<ExpectedDuration>> {
-        self.view_query(
-            "SELECT date, duration from expected_durations;",
+    fn view_missing_toggl_metadata(&mut self) -> Result<bool> {
+        Ok(self.query_row(
+            "SELECT EXISTS(SELECT 1 FROM time_entries \
+             WHERE id > 0 AND ( \
+                 (workspace_id IS NOT NULL AND NOT EXISTS(SELECT 1 FROM workspaces WHERE workspaces.id = workspace_id)) \
+                 OR (project_id IS NOT NULL AND NOT EXISTS(SELECT 1 FROM projects WHERE projects.id = project_id)) \
+             ));",
             params![],
+            |row| row.get(0),
+        )?)
+    }
+
+    fn list_expected_durations(&mut self, start: NaiveDate, end: NaiveDate) -> Result<Vec<ExpectedDuration>> {
+        self.view_query(
+            "SELECT DATE(date) AS date, duration, source FROM expected_duration WHERE DATE(date) BETWEEN ? AND ? ORDER BY date;",
+            params![start, end],
             |row| Ok(crate::models::ExpectedDuration {
                 date: row.get("date")?,
                 duration: row.get("duration")?,
+                source: row.get("source")?,
+            }),
+        )
+    }
+
+    fn list_holidays(&mut self, start: NaiveDate, end: NaiveDate) -> Result<Vec<Holiday>> {
+        self.view_query(
+            "SELECT date, name FROM holidays WHERE date BETWEEN ? AND ? ORDER BY date;",
+            params![start, end],
+            |row| Ok(Holiday {
+                date: row.get("date")?,
+                name: row.get("name")?,
+            }),
+        )
+    }
+
+    fn list_absences(&mut self, start: NaiveDate, end: NaiveDate) -> Result<Vec<Absence>> {
+        self.view_query(
+            "SELECT date, kind, fraction FROM absences WHERE DATE(date) BETWEEN ? AND ? ORDER BY date, kind;",
+            params![start, end],
+            |row| Ok(Absence {
+                date: row.get("date")?,
+                kind: row.get("kind")?,
+                fraction: row.get("fraction")?,
             }),
         )
     }
 
-    fn view_expected_durations_by_date(&mut self) -> Result<HashMap<NaiveDate, Duration>> {
-        todo!()
+    fn view_first_date(&mut self) -> Result<Option<NaiveDate>> {
+        Ok(self.query_row(
+            "SELECT MIN(DATE(start)) FROM time_entries;",
+            params![],
+            |row| row.get(0),
+        )?)
     }
 
-    fn view_event_by_date(&mut self) -> Result<HashMap<NaiveDate, Vec<Event>>> {
-        let events = self.list_events()?;
-        let mut map = HashMap::new();
-        for event in events {
-            let date = event.time.date_naive();
-            map.entry(date).or_insert(vec![event]);
+    fn view_tracking_start(&mut self) -> Result<Option<NaiveDate>> {
+        match self.view_setting(TRACKING_START_KEY)? {
+            Some(start) => { Ok(Some(start.parse()?)) }
+            None => { self.view_first_date() }
         }
-        Ok(map)
     }
 
-    fn view_events_where_date_eq(&mut self, date: NaiveDate) -> Result<Vec<Event>> {
+    fn update_tracking_start(&mut self, start: Option<NaiveDate>, initial_saldo: Duration) -> Result<()> {
+        match start {
+            None => { self.execute("DELETE FROM settings WHERE key = ?;", params![TRACKING_START_KEY])?; }
+            Some(start) => { self.update_setting(TRACKING_START_KEY, &start.to_string())?; }
+        }
+
+        self.execute("DELETE FROM saldo_adjustments WHERE source = 'initial';", params![])?;
+        if let (false, Some(start)) = (initial_saldo.is_zero(), self.view_tracking_start()?) {
+            self.insert_saldo_adjustment(&SaldoAdjustment {
+                id: 0,
+                date: start,
+                amount: crate::duration_newtype::Duration::of(initial_saldo),
+                reason: "initial saldo".to_string(),
+                source: "initial".to_string(),
+            })?;
+        }
+        Ok(())
+    }
+
+    fn view_presence_intervals(&mut self, start: NaiveDate, end: NaiveDate) -> Result<Vec<PresenceInterval>> {
         self.view_query(
-            "select instant, location from office_location GROUP BY DATE(instant), location HAVING DATE(instant) = ?;",
-            params![date],
-            |row| Ok(crate::models::Event {
-                time: row.get("instant")?,
-                name: row.get("location")?,
-            }),
+            &format!("SELECT {PRESENCE_INTERVAL_COLUMNS} FROM presence_intervals WHERE date BETWEEN ? AND ? ORDER BY start;"),
+            params![start, end],
+            presence_interval_row,
         )
     }
 
-    fn view_event_names(&mut self) -> Result<Vec<String>> {
+    fn list_presence_intervals(&mut self) -> Result<Vec<PresenceInterval>> {
         self.view_query(
-            "select DISTINCT(location) as name from office_location;",
+            &format!("SELECT {PRESENCE_INTERVAL_COLUMNS} FROM presence_intervals ORDER BY start;"),
             params![],
-            |row| row.get("name"),
+            presence_interval_row,
         )
     }
 
+    fn insert_location_intervals(&mut self, intervals: &[PresenceInterval]) -> Result<()> {
+        let tx = self.transaction()?;
+        for interval in intervals {
+            // the interval replaces the detections and intervals it covers, so importing twice counts them once
+            let params = params![interval.location, interval.start, interval.stop];
+            tx.execute("DELETE FROM office_location WHERE location = ? AND instant BETWEEN ? AND ?;", params)?;
+            tx.execute("DELETE FROM location_intervals WHERE location = ? AND start BETWEEN ? AND ?;", params)?;
+            tx.execute(
+                "INSERT INTO location_intervals (location, start, stop, detections) VALUES (?, ?, ?, ?);",
+                params![interval.location, interval.start, interval.stop, interval.detections],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn update_max_gap(&mut self, max_gap: Duration) -> Result<()> {
+        self.update_setting(MAX_GAP_KEY, &max_gap.num_seconds().to_string())
+    }
+
+    fn compact_detections(&mut self, before: DateTime<Utc>) -> Result<(usize, usize)> {
+        let tx = self.transaction()?;
+        // start and stop are kept as text, so they match the instants of the detections exactly
+        let intervals: Vec<(String, String, String, i64)> = tx
+            .prepare("SELECT location, start, stop, detections FROM presence_intervals WHERE julianday(stop) < julianday(?);")?
+            .query_map(params![before], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut removed = 0;
+        let mut written = 0;
+        for (location, start, stop, detections) in &intervals {
+            let deleted = tx.execute(
+                "DELETE FROM office_location WHERE location = ? AND instant BETWEEN ? AND ?;",
+                params![location, start, stop],
+            )?;
+            // the interval is compacted already
+            if deleted == 0 {
+                continue;
+            }
+            removed += deleted;
+            written += 1;
+            tx.execute(
+                "INSERT INTO location_intervals (location, start, stop, detections) VALUES (?, ?, ?, ?);",
+                params![location, start, stop, detections],
+            )?;
+        }
+        tx.commit()?;
+        Ok((removed, written))
+    }
+
     fn view_timesheet(&mut self, start: NaiveDate, end: NaiveDate) -> Result<TimeSheet> {
         debug!("Loading timesheet from {} to {}.", start, end);
         let timesheet = self.view_query(
-            "SELECT date, actual_duration, expected_duration, delta, saldo, normalized_start_of_business, normalized_end_of_business, events FROM timesheet WHERE timesheet.date BETWEEN ? AND ?;",
+            &format!("SELECT {TIMESHEET_COLUMNS} FROM timesheet \
+             WHERE timesheet.date BETWEEN ? AND ? AND (actual_duration > 0 OR expected_duration > 0 \
+             OR EXISTS (SELECT 1 FROM saldo_adjustments WHERE DATE(saldo_adjustments.date) = timesheet.date));"),
             params![start, end],
-            |row| Ok(crate::models::TimeSheetRow {
-                date: row.get("date")?,
-                actual_duration: row.get("actual_duration")?,
-                expected_duration: row.get("expected_duration")?,
-                delta: row.get("delta")?,
-                saldo: row.get("saldo")?,
-                normalized_start_of_business: row.get("normalized_start_of_business")?,
-                normalized_end_of_business: row.get("normalized_end_of_business")?,
-                locations: row.get("events").unwrap_or("".to_string()),
-            }),
+            timesheet_row,
         )?;
 
         Ok(timesheet)
     }
 
     fn view_full_timesheet(&mut self, start: NaiveDate, end: NaiveDate) -> Result<TimeSheet> {
-        let timesheet = self.view_timesheet(start, end)?;
+        let timesheet = self.view_query(
+            &format!("SELECT {TIMESHEET_COLUMNS} FROM timesheet WHERE timesheet.date BETWEEN ? AND ?;"),
+            params![start, end],
+            timesheet_row,
+        )?;
 
         if !timesheet.is_empty() {
             let start = timesheet[0].date;
             let end = timesheet.last().unwrap().date + Duration::days(1);
             debug!("Printing timesheet from {} to {}.", start, end);
 
+            let holidays: HashMap<NaiveDate, String> = self.list_holidays(start, end)?
+                .into_iter()
+                .map(|holiday| (holiday.date, holiday.name))
+                .collect();
+
             let mut full_timesheet: TimeSheet = Vec::new();
             let mut index = 0;
             for days in 0..(end - start).num_days() {
                 let current_date = start + Duration::days(days);
                 if current_date < timesheet[index].date {
-                    full_timesheet.push(crate::models::TimeSheetRow::empty(current_date));
+                    let mut row = crate::models::TimeSheetRow::filler(current_date);
+                    if let Some(name) = holidays.get(&current_date) {
+                        row.locations = name.clone();
+                    }
+                    full_timesheet.push(row);
                 } else {
                     full_timesheet.push(timesheet[index].clone());
                     index += 1;
@@ -325,31 +897,99 @@ impl DataStore for Connection {
 
     fn view_timesheet_export(&mut self) -> Result<TimeSheet> {
         let timesheet = self.view_query(
-            "SELECT date, actual_duration, expected_duration, delta, saldo, normalized_start_of_business, normalized_end_of_business, events FROM timesheet;",
+            &format!("SELECT {TIMESHEET_COLUMNS} FROM timesheet;"),
             params![],
-            |row| Ok(crate::models::TimeSheetRow {
-                date: row.get("date")?,
-                actual_duration: row.get("actual_duration")?,
-                expected_duration: row.get("expected_duration")?,
-                delta: row.get("delta")?,
-                saldo: row.get("saldo")?,
-                normalized_start_of_business: row.get("normalized_start_of_business")?,
-                normalized_end_of_business: row.get("normalized_end_of_business")?,
-                locations: row.get("events")?,
-            }),
+            timesheet_row,
         )?;
 
         Ok(timesheet)
     }
 }
 
+/// all columns of the time_entries table needed by time_entry_row
+static TIME_ENTRY_COLUMNS: &str = "id, description, start, stop, project_id, workspace_id, billable, tags";
+
+fn time_entry_row(row: &Row<'_>) -> rusqlite::Result<TimeEntry> {
+    let tags: String = row.get("tags")?;
+    Ok(TimeEntry {
+        id: row.get("id")?,
+        description: row.get("description")?,
+        start: row.get("start")?,
+        stop: row.get("stop")?,
+        project_id: row.get("project_id")?,
+        workspace_id: row.get("workspace_id")?,
+        billable: row.get("billable")?,
+        tags: serde_json::from_str(&tags)
+            .map_err(|error| rusqlite::Error::FromSqlConversionFailure(0, Type::Text, Box::new(error)))?,
+    })
+}
+
+/// all columns of the presence_intervals view needed by presence_interval_row
+static PRESENCE_INTERVAL_COLUMNS: &str = "date, location, start, stop, detections";
+
+fn presence_interval_row(row: &Row<'_>) -> rusqlite::Result<PresenceInterval> {
+    Ok(PresenceInterval {
+        date: row.get("date")?,
+        location: row.get("location")?,
+        start: row.get("start")?,
+        stop: row.get("stop")?,
+        detections: row.get("detections")?,
+    })
+}
+
+/// all columns of the timesheet view needed by timesheet_row
+static TIMESHEET_COLUMNS: &str = "date, actual_duration, expected_duration, delta, saldo, start_of_business, end_of_business, break_duration, events, projects";
+
+fn timesheet_row(row: &Row<'_>) -> rusqlite::Result<TimeSheetRow> {
+    Ok(TimeSheetRow {
+        date: row.get("date")?,
+        actual_duration: row.get("actual_duration")?,
+        expected_duration: row.get("expected_duration")?,
+        delta: row.get("delta")?,
+        saldo: row.get("saldo")?,
+        normalized_start_of_business: Default::default(),
+        normalized_end_of_business: Default::default(),
+        start_of_business: row.get("start_of_business")?,
+        end_of_business: row.get("end_of_business")?,
+        break_duration: row.get("break_duration")?,
+        locations: row.get::<_, Option<String>>("events")?.unwrap_or_default(),
+        warnings: "".to_string(),
+        projects: row.get::<_, Option<String>>("projects")?.unwrap_or_default(),
+        filled: false,
+    })
+}
+
 
 #[cfg(test)]
 mod tests {
-    use chrono::{Duration, NaiveDate, Utc};
+    use chrono::{DateTime, Duration, NaiveDate, Utc};
     use rusqlite::Connection;
-    use crate::datastore::{DataStore};
-    use crate::models::TimeEntry;
+    use crate::config::{Breaks, WorkWeek};
+    use crate::datastore::{DataStore, MIGRATIONS};
+    use crate::models::{Absence, AbsenceKind, Client, Event, ExpectedDuration, Holiday, Project, SaldoAdjustment, TimeEntry, Workspace};
+
+    /// a time entry without project and tags from one time of the day to another
+    fn time_entry(id: i64, day: NaiveDate, from: (u32, u32), to: (u32, u32)) -> TimeEntry {
+        TimeEntry {
+            id,
+            description: None,
+            start: day.and_hms_opt(from.0, from.1, 0).unwrap().and_local_timezone(Utc).unwrap(),
+            stop: Some(day.and_hms_opt(to.0, to.1, 0).unwrap().and_local_timezone(Utc).unwrap()),
+            project_id: None,
+            workspace_id: None,
+            billable: false,
+            tags: vec![],
+        }
+    }
+
+    /// four hours expected every day
+    fn workweek() -> WorkWeek {
+        WorkWeek {
+            default_expected_duration_seconds: 14400,
+            schedule: None,
+            periods: vec![],
+        }
+    }
 
     #[test]
     fn test_format() {
@@ -359,21 +999,491 @@ mod tests {
         let begin = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
         for id in 0..10 {
             let day = begin + Duration::days(id);
-            let time_entry = TimeEntry {
+            connection.insert_time_entry(&time_entry(id, day, (8, 0), (12, 0))).unwrap();
+        }
+        let end = begin + Duration::days(10);
+        let workweek = WorkWeek { default_expected_duration_seconds: 42, ..workweek() };
+        connection.insert_scheduled_expected_durations(&workweek, end).unwrap();
+        assert_eq!(10, connection.list_time_entry_details(begin, end).unwrap().len());
+        assert_eq!(10, connection.view_timesheet(begin, end).unwrap().len());
+
+        println!("{:?}", connection.view_timesheet(begin, end).unwrap());
+    }
+
+    #[test]
+    fn test_holidays_are_free() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.run_migrations().unwrap();
+
+        let christmas = NaiveDate::from_ymd_opt(2023, 12, 25).unwrap();
+        connection.insert_time_entry(&time_entry(1, christmas, (8, 0), (9, 0))).unwrap();
+        connection.replace_holidays(&[Holiday { date: christmas, name: "1. Weihnachtstag".to_string() }]).unwrap();
+
+        let workweek = WorkWeek { default_expected_duration_seconds: 27000, ..workweek() };
+        connection.insert_scheduled_expected_durations(&workweek, christmas + Duration::days(1)).unwrap();
+
+        let timesheet = connection.view_timesheet(christmas, christmas + Duration::days(1)).unwrap();
+        assert_eq!(0, timesheet[0].expected_duration.chrono_duration.num_seconds());
+        assert_eq!("1. Weihnachtstag", timesheet[0].locations);
+        assert_eq!(27000, timesheet[1].expected_duration.chrono_duration.num_seconds());
+    }
+
+    #[test]
+    fn test_absences_count_as_fulfilled() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.run_migrations().unwrap();
+
+        let monday = NaiveDate::from_ymd_opt(2023, 7, 3).unwrap();
+        let tuesday = monday + Duration::days(1);
+        connection.insert_time_entry(&time_entry(1, monday, (8, 0), (10, 0))).unwrap();
+        connection.insert_absences(&[
+            Absence { date: monday, kind: AbsenceKind::Vacation, fraction: 0.5 },
+            Absence { date: tuesday, kind: AbsenceKind::Sick, fraction: 1.0 },
+        ]).unwrap();
+
+        connection.insert_scheduled_expected_durations(&workweek(), tuesday).unwrap();
+
+        let timesheet = connection.view_timesheet(monday, tuesday).unwrap();
+        assert_eq!(0, timesheet[0].delta.chrono_duration.num_seconds());
+        assert_eq!("vacation (half day)", timesheet[0].locations);
+        assert_eq!(0, timesheet[1].saldo.chrono_duration.num_seconds());
+    }
+
+    #[test]
+    fn test_absences_of_different_kinds_on_the_same_day() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.run_migrations().unwrap();
+
+        let monday = NaiveDate::from_ymd_opt(2023, 7, 3).unwrap();
+        let tuesday = monday + Duration::days(1);
+        connection.insert_absences(&[
+            Absence { date: monday, kind: AbsenceKind::Vacation, fraction: 0.5 },
+            Absence { date: monday, kind: AbsenceKind::Sick, fraction: 0.5 },
+            Absence { date: tuesday, kind: AbsenceKind::Vacation, fraction: 1.0 },
+            Absence { date: tuesday, kind: AbsenceKind::Sick, fraction: 1.0 },
+        ]).unwrap();
+        for date in [monday, tuesday] {
+            connection.insert_expected_duration(ExpectedDuration {
+                date,
+                duration: crate::duration_newtype::Duration::of(Duration::hours(4)),
+                source: "schedule".to_string(),
+            }).unwrap();
+        }
+
+        assert_eq!(4, connection.list_absences(monday, tuesday).unwrap().len());
+        let timesheet = connection.view_timesheet(monday, tuesday).unwrap();
+        assert_eq!("sick (half day), vacation (half day)", timesheet[0].locations);
+        assert_eq!(0, timesheet[0].delta.chrono_duration.num_seconds());
+        // two full days of absence still only cover the expectation once
+        assert_eq!(0, timesheet[1].delta.chrono_duration.num_seconds());
+
+        // recording the same kind again replaces it
+        connection.insert_absences(&[Absence { date: monday, kind: AbsenceKind::Sick, fraction: 1.0 }]).unwrap();
+        assert_eq!(4, connection.list_absences(monday, tuesday).unwrap().len());
+    }
+
+    #[test]
+    fn test_expected_duration_overrides() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.run_migrations().unwrap();
+
+        let monday = NaiveDate::from_ymd_opt(2023, 7, 3).unwrap();
+        let saturday = monday + Duration::days(5);
+        connection.insert_time_entry(&time_entry(1, monday, (8, 0), (12, 0))).unwrap();
+        connection.insert_expected_duration(ExpectedDuration {
+            date: saturday,
+            duration: crate::duration_newtype::Duration::of(Duration::hours(2)),
+            source: "manual".to_string(),
+        }).unwrap();
+
+        // the override survives refreshing the schedule and counts without time entries
+        connection.insert_scheduled_expected_durations(&workweek(), saturday).unwrap();
+        let timesheet = connection.view_timesheet(saturday, saturday).unwrap();
+        assert_eq!(-7200, timesheet[0].delta.chrono_duration.num_seconds());
+
+        let expected_durations = connection.list_expected_durations(monday, saturday).unwrap();
+        assert_eq!(6, expected_durations.len());
+        assert_eq!("manual", expected_durations[5].source);
+
+        assert_eq!(1, connection.remove_expected_duration_overrides(monday, saturday).unwrap());
+        assert!(connection.view_timesheet(saturday, saturday).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_saldo_adjustments() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.run_migrations().unwrap();
+
+        // six hours of overtime on the last friday of 2022
+        let friday = NaiveDate::from_ymd_opt(2022, 12, 30).unwrap();
+        let monday = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let tuesday = monday + Duration::days(1);
+        connection.insert_time_entry(&time_entry(1, friday, (8, 0), (18, 0))).unwrap();
+        connection.insert_absences(&[Absence { date: monday, kind: AbsenceKind::Compensation, fraction: 1.0 }]).unwrap();
+        connection.insert_scheduled_expected_durations(&workweek(), tuesday).unwrap();
+
+        // only two hours are carried over and the compensation day is taken from them
+        connection.update_carry_over_cap(Some(Duration::hours(2)), tuesday).unwrap();
+        connection.update_carry_over_cap(Some(Duration::hours(2)), tuesday).unwrap();
+        assert_eq!(Duration::hours(6), connection.view_saldo(friday).unwrap());
+        assert_eq!(Duration::hours(2), connection.view_saldo(monday - Duration::days(1)).unwrap());
+        assert_eq!(Duration::hours(-2), connection.view_saldo(monday).unwrap());
+
+        let id = connection.insert_saldo_adjustment(&SaldoAdjustment {
+            id: 0,
+            date: tuesday,
+            amount: crate::duration_newtype::Duration::of(Duration::hours(1)),
+            reason: "correction".to_string(),
+            source: "manual".to_string(),
+        }).unwrap();
+        assert_eq!(Duration::hours(-5), connection.view_saldo(tuesday).unwrap());
+        assert_eq!("saldo correction", connection.view_timesheet(tuesday, tuesday).unwrap()[0].locations);
+
+        let adjustments = connection.list_saldo_adjustments(friday, tuesday).unwrap();
+        assert_eq!(2, adjustments.len());
+        assert_eq!("cap", adjustments[0].source);
+        assert_eq!(-4 * 3600, adjustments[0].amount.chrono_duration.num_seconds());
+
+        // calculated adjustments can not be removed by hand
+        assert_eq!(0, connection.remove_saldo_adjustment(adjustments[0].id).unwrap());
+        assert_eq!(1, connection.remove_saldo_adjustment(id).unwrap());
+        connection.update_carry_over_cap(None, tuesday).unwrap();
+        assert_eq!(Duration::hours(-2), connection.view_saldo(tuesday).unwrap());
+
+        // a weekend day only carrying an adjustment is still shown
+        let saturday = monday + Duration::days(5);
+        connection.insert_saldo_adjustment(&SaldoAdjustment {
+            id: 0,
+            date: saturday,
+            amount: crate::duration_newtype::Duration::of(Duration::hours(2)),
+            reason: "weekend correction".to_string(),
+            source: "manual".to_string(),
+        }).unwrap();
+        let timesheet = connection.view_timesheet(saturday, saturday).unwrap();
+        assert_eq!(1, timesheet.len());
+        assert_eq!("saldo weekend correction", timesheet[0].locations);
+        assert_eq!(Duration::hours(2), timesheet[0].saldo.chrono_duration - connection.view_saldo(tuesday).unwrap());
+    }
+
+    #[test]
+    fn test_tracking_start() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.run_migrations().unwrap();
+
+        let before = NaiveDate::from_ymd_opt(2023, 5, 31).unwrap();
+        let start = before + Duration::days(1);
+        for (id, day) in [(1, before), (2, start)] {
+            connection.insert_time_entry(&time_entry(id, day, (8, 0), (13, 0))).unwrap();
+        }
+        let workweek = workweek();
+
+        // history before the tracking start does not count, the initial saldo does
+        connection.update_tracking_start(Some(start), Duration::hours(10)).unwrap();
+        connection.insert_scheduled_expected_durations(&workweek, start).unwrap();
+        assert_eq!(Some(start), connection.view_tracking_start().unwrap());
+        assert!(connection.view_timesheet(before, before).unwrap().is_empty());
+        assert_eq!(11 * 3600, connection.view_timesheet(start, start).unwrap()[0].saldo.chrono_duration.num_seconds());
+
+        connection.update_tracking_start(None, Duration::zero()).unwrap();
+        connection.insert_scheduled_expected_durations(&workweek, start).unwrap();
+        assert_eq!(Some(before), connection.view_tracking_start().unwrap());
+        assert_eq!(2 * 3600, connection.view_saldo(start).unwrap().num_seconds());
+    }
+
+    #[test]
+    fn test_presence_intervals() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.run_migrations().unwrap();
+
+        let day = NaiveDate::from_ymd_opt(2023, 7, 3).unwrap();
+        for (location, (hour, minute)) in [
+            ("office", (8, 0)), ("office", (8, 5)), ("office", (8, 10)),
+            ("office", (9, 0)),
+            ("home", (17, 30)), ("home", (17, 35)),
+        ] {
+            connection.insert_event(&Event {
+                time: day.and_hms_opt(hour, minute, 0).unwrap().and_local_timezone(Utc).unwrap(),
+                name: location.to_string(),
+            }).unwrap();
+        }
+        connection.insert_expected_duration(ExpectedDuration {
+            date: day,
+            duration: crate::duration_newtype::Duration::of(Duration::hours(8)),
+            source: "manual".to_string(),
+        }).unwrap();
+
+        // the detection at nine is too far away from the others
+        connection.update_max_gap(Duration::minutes(15)).unwrap();
+        let intervals = connection.view_presence_intervals(day, day).unwrap();
+        assert_eq!(3, intervals.len());
+        assert_eq!(Duration::minutes(10), intervals[0].stop - intervals[0].start);
+        assert_eq!(intervals[1].start, intervals[1].stop);
+        assert_eq!("home", intervals[2].location);
+        assert_eq!("office 00:10, home 00:05", connection.view_timesheet(day, day).unwrap()[0].locations);
+
+        connection.update_max_gap(Duration::hours(1)).unwrap();
+        assert_eq!(2, connection.view_presence_intervals(day, day).unwrap().len());
+        assert_eq!("office 01:00, home 00:05", connection.view_timesheet(day, day).unwrap()[0].locations);
+
+        // a stay ends when a run of detect finds another location without it
+        let next_day = day + Duration::days(1);
+        let at = |hour, minute| next_day.and_hms_opt(hour, minute, 0).unwrap().and_local_timezone(Utc).unwrap();
+        for (location, time) in [
+            ("office", at(8, 0)), ("vpn", at(8, 0)), ("office", at(8, 5)), ("vpn", at(8, 5)),
+            ("home", at(8, 10)), ("office", at(8, 15)), ("office", at(23, 55)),
+        ] {
+            connection.insert_event(&Event { time, name: location.to_string() }).unwrap();
+        }
+        let intervals = connection.view_presence_intervals(next_day, next_day).unwrap();
+        let stays: Vec<(&str, DateTime<Utc>, DateTime<Utc>)> = intervals
+            .iter()
+            .map(|interval| (interval.location.as_str(), interval.start, interval.stop))
+            .collect();
+        assert_eq!(5, stays.len());
+        assert!(stays.contains(&("office", at(8, 0), at(8, 5))));
+        assert!(stays.contains(&("vpn", at(8, 0), at(8, 5))));
+        assert!(stays.contains(&("office", at(8, 15), at(8, 15))));
+        assert!(intervals.iter().all(|interval| interval.date == next_day));
+    }
+
+    #[test]
+    fn test_compaction_keeps_presence_intervals() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.run_migrations().unwrap();
+        connection.update_max_gap(Duration::minutes(15)).unwrap();
+
+        // the run at 08:05 only finds home, so the office stays before and after it are separate
+        let day = NaiveDate::from_ymd_opt(2023, 7, 3).unwrap();
+        let at = |hour, minute| day.and_hms_opt(hour, minute, 0).unwrap().and_local_timezone(Utc).unwrap();
+        for (location, time) in [
+            ("home", at(8, 0)), ("office", at(8, 0)), ("home", at(8, 5)),
+            ("home", at(8, 10)), ("office", at(8, 10)), ("office", at(8, 20)),
+        ] {
+            connection.insert_event(&Event { time, name: location.to_string() }).unwrap();
+        }
+        let intervals = connection.list_presence_intervals().unwrap();
+        assert_eq!(3, intervals.len());
+
+        // compacting only some of the stays and then all of them does not change the history
+        for before in [at(8, 7), at(8, 15), at(23, 0)] {
+            connection.compact_detections(before).unwrap();
+            assert_eq!(intervals, connection.list_presence_intervals().unwrap());
+        }
+        assert!(connection.list_events().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_compact_detections() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.run_migrations().unwrap();
+        connection.update_max_gap(Duration::minutes(15)).unwrap();
+
+        let day = NaiveDate::from_ymd_opt(2023, 7, 3).unwrap();
+        let at = |hour, minute| day.and_hms_opt(hour, minute, 0).unwrap().and_local_timezone(Utc).unwrap();
+        for (location, time) in [
+            ("office", at(8, 0)), ("office", at(8, 5)), ("office", at(8, 10)),
+            ("office", at(9, 0)), ("home", at(17, 30)),
+        ] {
+            connection.insert_event(&Event { time, name: location.to_string() }).unwrap();
+        }
+
+        // the stay at home may still go on, so it is kept raw
+        let intervals = connection.list_presence_intervals().unwrap();
+        assert_eq!((4, 2), connection.compact_detections(at(12, 0)).unwrap());
+        assert_eq!(1, connection.list_events().unwrap().len());
+        assert_eq!(intervals, connection.list_presence_intervals().unwrap());
+        assert_eq!(3, intervals.len());
+        assert_eq!(at(8, 0), intervals[0].start);
+        assert_eq!(at(8, 10), intervals[0].stop);
+
+        // compacted stays are complete, a later detection starts a new one
+        connection.insert_event(&Event { time: at(9, 10), name: "office".to_string() }).unwrap();
+        assert_eq!(at(9, 0), connection.view_presence_intervals(day, day).unwrap()[1].stop);
+        let intervals = connection.list_presence_intervals().unwrap();
+        assert_eq!((2, 2), connection.compact_detections(at(23, 0)).unwrap());
+        assert!(connection.list_events().unwrap().is_empty());
+        assert_eq!(intervals, connection.list_presence_intervals().unwrap());
+        // compacting again finds nothing left to do
+        assert_eq!((0, 0), connection.compact_detections(at(23, 0)).unwrap());
+        assert_eq!(4, intervals.len());
+        assert_eq!(at(9, 10), intervals[2].start);
+        assert_eq!(at(9, 10), intervals[2].stop);
+
+        // compacted intervals are still exported and can be imported again
+        let exported = connection.list_presence_intervals().unwrap();
+        assert_eq!(4, exported.len());
+        assert_eq!(3, exported[0].detections);
+        let mut imported = Connection::open_in_memory().unwrap();
+        imported.run_migrations().unwrap();
+        imported.update_max_gap(Duration::minutes(15)).unwrap();
+        imported.insert_location_intervals(&exported).unwrap();
+        assert_eq!(exported, imported.list_presence_intervals().unwrap());
+        imported.insert_location_intervals(&exported).unwrap();
+        assert_eq!(exported, imported.list_presence_intervals().unwrap());
+    }
+
+    #[test]
+    fn test_breaks_taken() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.run_migrations().unwrap();
+
+        let day = NaiveDate::from_ymd_opt(2023, 7, 3).unwrap();
+        for (id, (start, stop)) in [((8, 0), (12, 0)), ((12, 15), (17, 0))].into_iter().enumerate() {
+            connection.insert_time_entry(&time_entry(id as i64, day, start, stop)).unwrap();
+        }
+
+        let mut timesheet = connection.view_timesheet(day, day).unwrap();
+        assert_eq!(15 * 60, timesheet[0].break_duration.chrono_duration.num_seconds());
+
+        timesheet[0].apply_breaks(&Breaks::default());
+        assert_eq!("break < 30m", timesheet[0].warnings);
+    }
+
+    #[test]
+    fn test_local_ids_do_not_collide() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.run_migrations().unwrap();
+        assert_eq!(-1, connection.next_local_time_entry_id().unwrap());
+
+        let start = Utc::now();
+        for id in [42, connection.next_local_time_entry_id().unwrap()] {
+            connection.insert_time_entry(&TimeEntry {
                 id,
                 description: None,
-                start: day.and_hms_opt(8, 0, 0).unwrap().and_local_timezone(Utc).unwrap(),
-                stop: Some(day.and_hms_opt(12, 0, 0).unwrap().and_local_timezone(Utc).unwrap()),
+                start,
+                stop: None,
                 project_id: None,
                 workspace_id: None,
-            };
-            connection.insert_time_entry(&time_entry).unwrap();
+                billable: false,
+                tags: vec![],
+            }).unwrap();
         }
-        let end = begin + Duration::days(10);
-        connection.insert_default_expected_duration(Duration::seconds(42)).unwrap();
-        assert_eq!(10, connection.list_time_entries().unwrap().len());
-        assert_eq!(9, connection.view_timesheet(begin, end).unwrap().len());
+        assert_eq!(-2, connection.next_local_time_entry_id().unwrap());
+    }
 
-        println!("{:?}", connection.view_timesheet(begin, end).unwrap());
+    #[test]
+    fn test_remove_deleted_toggl_entries() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.run_migrations().unwrap();
+
+        let day = NaiveDate::from_ymd_opt(2023, 7, 3).unwrap();
+        for id in [-1, 1, 2] {
+            connection.insert_time_entry(&TimeEntry { stop: None, ..time_entry(id, day, (8, 0), (8, 0)) }).unwrap();
+        }
+
+        // entries without workspace and project do not need any metadata
+        assert!(!connection.view_missing_toggl_metadata().unwrap());
+
+        // local time entries are never removed
+        assert_eq!(1, connection.remove_toggl_time_entries_except(day, day + Duration::days(1), &[2]).unwrap());
+        assert_eq!(2, connection.list_time_entry_details(day, day).unwrap().len());
+
+        connection.update_setting("toggl.last_sync", "2023-07-03T08:00:00+00:00").unwrap();
+        assert_eq!(Some("2023-07-03T08:00:00+00:00".to_string()), connection.view_setting("toggl.last_sync").unwrap());
+    }
+
+    #[test]
+    fn test_time_entry_details() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.run_migrations().unwrap();
+
+        let day = NaiveDate::from_ymd_opt(2023, 7, 3).unwrap();
+        connection.insert_workspaces(&[Workspace { id: 1, name: "Work".to_string() }]).unwrap();
+        connection.insert_clients(&[Client { id: 2, workspace_id: 1, name: "ACME".to_string() }]).unwrap();
+        connection.insert_projects(&[Project {
+            id: 3,
+            workspace_id: 1,
+            client_id: Some(2),
+            name: "Rocket".to_string(),
+            color: None,
+            active: true,
+        }]).unwrap();
+        connection.insert_time_entry(&TimeEntry {
+            id: 4,
+            description: None,
+            start: day.and_hms_opt(8, 0, 0).unwrap().and_local_timezone(Utc).unwrap(),
+            stop: None,
+            project_id: Some(3),
+            workspace_id: Some(1),
+            billable: true,
+            tags: vec!["rust".to_string()],
+        }).unwrap();
+        assert!(!connection.view_missing_toggl_metadata().unwrap());
+
+        let details = connection.list_time_entry_details(day, day).unwrap();
+        assert_eq!(Some("ACME".to_string()), details[0].client);
+        assert_eq!(Some("Rocket".to_string()), details[0].project);
+        assert_eq!(vec!["rust".to_string()], details[0].time_entry.tags);
+        assert_eq!("Rocket", connection.view_timesheet(day, day).unwrap()[0].projects);
+    }
+
+    #[test]
+    fn test_imported_time_entries() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.run_migrations().unwrap();
+        let start = NaiveDate::from_ymd_opt(2023, 3, 10).unwrap().and_hms_opt(8, 0, 0).unwrap().and_local_timezone(Utc).unwrap();
+
+        let project_id = connection.find_or_insert_local_project("Migration").unwrap();
+        assert_eq!(-1, project_id);
+        assert_eq!(project_id, connection.find_or_insert_local_project("Migration").unwrap());
+
+        let time_entry = TimeEntry {
+            id: connection.next_local_time_entry_id().unwrap(),
+            description: Some("imported".to_string()),
+            start,
+            stop: Some(start + Duration::hours(2)),
+            project_id: Some(project_id),
+            workspace_id: None,
+            billable: false,
+            tags: vec![],
+        };
+        connection.insert_imported_time_entries("clockify", &[("a1".to_string(), time_entry.clone())]).unwrap();
+        connection.insert_imported_time_entries("clockify", &[("a1".to_string(), time_entry)]).unwrap();
+
+        let ids = connection.list_imported_time_entry_ids("clockify").unwrap();
+        assert_eq!(Some(&-1), ids.get("a1"));
+        assert!(connection.list_imported_time_entry_ids("toggl").unwrap().is_empty());
+
+        let details = connection.list_time_entry_details(start.date_naive(), start.date_naive()).unwrap();
+        assert_eq!(1, details.len());
+        assert_eq!(Some("Migration".to_string()), details[0].project);
+    }
+
+    #[test]
+    fn test_migrations_are_idempotent() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.run_migrations().unwrap();
+        let version = connection.schema_version().unwrap();
+        assert_eq!(MIGRATIONS.len(), version);
+
+        connection.run_migrations().unwrap();
+        assert_eq!(version, connection.schema_version().unwrap());
+    }
+
+    #[test]
+    fn test_backup_into() {
+        let path = std::env::temp_dir().join(format!("papierkram-test-backup-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.run_migrations().unwrap();
+        connection.update_setting("toggl.last_sync", "2023-03-10T12:00:00+00:00").unwrap();
+        connection.backup_into(&path).unwrap();
+
+        let mut backup = Connection::open(&path).unwrap();
+        assert_eq!(MIGRATIONS.len(), backup.schema_version().unwrap());
+        assert!(backup.check_integrity().unwrap());
+        assert_eq!(Some("2023-03-10T12:00:00+00:00".to_string()), backup.view_setting("toggl.last_sync").unwrap());
+
+        // an existing backup is never overwritten
+        assert!(connection.backup_into(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_refuse_newer_schema() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.pragma_update(None, "user_version", MIGRATIONS.len() + 1).unwrap();
+        assert!(connection.run_migrations().is_err());
     }
 }
\ No newline at end of file
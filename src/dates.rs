@@ -1,15 +1,21 @@
 use std::cmp::{max, min};
-use chrono::{Datelike, DateTime, Duration, Local, NaiveTime, TimeZone, Utc};
+use chrono::{Datelike, DateTime, Duration, Local, NaiveDate, NaiveTime, TimeZone, Utc};
 
 /// Parses a user submitted date string with best effort.
-pub fn parse_date_time(date_str: &String) -> anyhow::Result<DateTime<Utc>> {
+pub fn parse_date_time(date_str: &str) -> anyhow::Result<DateTime<Utc>> {
     let default_time = NaiveTime::from_hms_opt(0, 0, 0).unwrap();
     dateparser::parse_with(date_str, &Local, default_time)
 }
 
 
+/// Parses a user submitted date string with best effort and returns the local date.
+pub fn parse_date(date_str: &str) -> anyhow::Result<NaiveDate> {
+    Ok(parse_date_time(date_str)?.with_timezone(&Local).date_naive())
+}
+
+
 pub fn parse_time_interval(start: &Option<String>, end: &Option<String>) -> (DateTime<Utc>, DateTime<Utc>) {
-    let end = end.as_ref()
+    let end = end.as_deref()
         .map(parse_date_time)
         .and_then(|x| x.ok())
         .unwrap_or(Utc::now() + Duration::days(1));
@@ -21,7 +27,7 @@ pub fn parse_time_interval(start: &Option<String>, end: &Option<String>) -> (Dat
         .expect("Could not get the start of the year!");
 
 
-    let start = start.as_ref()
+    let start = start.as_deref()
         .map(parse_date_time)
         .and_then(|x| x.ok())
         .unwrap_or(start_of_year);
@@ -29,3 +35,160 @@ pub fn parse_time_interval(start: &Option<String>, end: &Option<String>) -> (Dat
     let (start, end) = (min(start, end), max(start, end));
     (start, end)
 }
+
+
+/// Parses a date or a range of dates like 2023-04-03..2023-04-07, both ends inclusive
+pub fn parse_date_range(value: &str) -> anyhow::Result<(NaiveDate, NaiveDate)> {
+    let (start, end) = match value.split_once("..") {
+        None => { (parse_date(value)?, parse_date(value)?) }
+        Some((start, end)) => { (parse_date(start)?, parse_date(end)?) }
+    };
+    Ok((min(start, end), max(start, end)))
+}
+
+/// Parses durations like 4h30m, 90m, 4:30, 04:30:00 or decimal hours like 1.5 and 1,5.
+/// A leading minus makes the duration negative.
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Some(value) = value.strip_prefix('-') {
+        return parse_duration(value).map(|duration| -duration);
+    }
+    if value.contains(':') {
+        let parts: Vec<i64> = value
+            .split(':')
+            .map(|part| part.parse().ok())
+            .collect::<Option<Vec<i64>>>()?;
+        return match parts.as_slice() {
+            [hours, minutes] => { Some(Duration::hours(*hours) + Duration::minutes(*minutes)) }
+            [hours, minutes, seconds] => {
+                Some(Duration::hours(*hours) + Duration::minutes(*minutes) + Duration::seconds(*seconds))
+            }
+            _ => { None }
+        };
+    }
+    if let Ok(hours) = value.replace(',', ".").parse::<f64>() {
+        return Some(Duration::seconds((hours * 3600.0).round() as i64));
+    }
+
+    // a sequence of numbers with the units h, m and s
+    let mut duration = Duration::zero();
+    let mut number = String::new();
+    for character in value.chars().filter(|character| !character.is_whitespace()) {
+        if character.is_ascii_digit() || character == '.' {
+            number.push(character);
+            continue;
+        }
+        let amount: f64 = number.parse().ok()?;
+        let seconds = match character {
+            'h' => { amount * 3600.0 }
+            'm' => { amount * 60.0 }
+            's' => { amount }
+            _ => { return None; }
+        };
+        duration = duration + Duration::seconds(seconds.round() as i64);
+        number.clear();
+    }
+    match number.is_empty() && !value.is_empty() {
+        true => { Some(duration) }
+        false => { None }
+    }
+}
+
+/// A calendar period timesheets and reports can be grouped by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Period {
+    /// ISO week starting on monday
+    Week,
+    Month,
+    Year,
+}
+
+impl Period {
+    /// returns the first day of the period containing the date
+    pub fn start_of(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Week => {
+                date - Duration::days(date.weekday().num_days_from_monday() as i64)
+            }
+            Period::Month => {
+                date.with_day(1).unwrap()
+            }
+            Period::Year => {
+                date.with_ordinal(1).unwrap()
+            }
+        }
+    }
+
+    /// returns the last day of the period containing the date
+    pub fn end_of(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Week => {
+                self.start_of(date) + Duration::days(6)
+            }
+            Period::Month => {
+                let next_month = self.start_of(date) + Duration::days(31);
+                next_month.with_day(1).unwrap() - Duration::days(1)
+            }
+            Period::Year => {
+                NaiveDate::from_ymd_opt(date.year(), 12, 31).unwrap()
+            }
+        }
+    }
+
+    /// returns a label like 2023-W14, 2023-04 or 2023 for the period containing the date
+    pub fn label(&self, date: NaiveDate) -> String {
+        match self {
+            Period::Week => {
+                let week = date.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            Period::Month => {
+                date.format("%Y-%m").to_string()
+            }
+            Period::Year => {
+                date.format("%Y").to_string()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate};
+    use crate::dates::{parse_date_range, parse_duration, Period};
+
+    #[test]
+    fn test_period() {
+        let date = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+        assert_eq!(NaiveDate::from_ymd_opt(2022, 12, 26).unwrap(), Period::Week.start_of(date));
+        assert_eq!("2022-W52", Period::Week.label(date));
+        assert_eq!("2023-01", Period::Month.label(date));
+        assert_eq!(date, Period::Year.start_of(NaiveDate::from_ymd_opt(2023, 8, 17).unwrap()));
+        assert_eq!(NaiveDate::from_ymd_opt(2024, 2, 29).unwrap(), Period::Month.end_of(NaiveDate::from_ymd_opt(2024, 2, 10).unwrap()));
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), Period::Week.end_of(date));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(Some(Duration::minutes(270)), parse_duration("4h30m"));
+        assert_eq!(Some(Duration::minutes(270)), parse_duration("4h 30m"));
+        assert_eq!(Some(Duration::minutes(90)), parse_duration("90m"));
+        assert_eq!(Some(Duration::minutes(270)), parse_duration("4:30"));
+        assert_eq!(Some(Duration::seconds(5405)), parse_duration("01:30:05"));
+        assert_eq!(Some(Duration::minutes(90)), parse_duration("1,5"));
+        assert_eq!(Some(Duration::zero()), parse_duration("0"));
+        assert_eq!(Some(Duration::minutes(-90)), parse_duration("-1h30m"));
+        assert_eq!(Some(Duration::minutes(-270)), parse_duration("-4:30"));
+        assert_eq!(None, parse_duration("4x"));
+        assert_eq!(None, parse_duration("4h30"));
+        assert_eq!(None, parse_duration(""));
+    }
+
+    #[test]
+    fn test_parse_date_range() {
+        let (start, end) = parse_date_range("2023-04-07..2023-04-03").unwrap();
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 4, 3).unwrap(), start);
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 4, 7).unwrap(), end);
+        assert_eq!(start, parse_date_range("2023-04-03").unwrap().1);
+    }
+}
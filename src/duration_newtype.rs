@@ -40,6 +40,20 @@ impl Duration {
         let hours = cdur.num_hours().abs();
         format!("{sign}{hours:02}:{minutes:02}:{seconds:02}")
     }
+
+    /// formats the duration as HH:MM with a leading minus if negative
+    pub fn format_hours_minutes(&self) -> String {
+        let cdur = self.chrono_duration;
+        let sign = if cdur.num_seconds() < 0 { "-" } else { "" };
+        let minutes = (cdur.num_minutes() % 60).abs();
+        let hours = cdur.num_hours().abs();
+        format!("{sign}{hours:02}:{minutes:02}")
+    }
+
+    /// returns the duration in hours as a decimal number
+    pub fn hours(&self) -> f64 {
+        self.chrono_duration.num_seconds() as f64 / 3600.0
+    }
 }
 
 impl Display for Duration {
@@ -141,5 +155,9 @@ mod tests {
 
         let duration = Duration::of(chrono::Duration::seconds(-10));
         assert_eq!(duration.format_signed(), "-00:00:10");
+
+        let duration = Duration::of(chrono::Duration::seconds(-5430));
+        assert_eq!(duration.format_hours_minutes(), "-01:30");
+        assert_eq!(duration.hours(), -1.5083333333333333);
     }
 }
\ No newline at end of file
@@ -0,0 +1,269 @@
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::{Datelike, Local, NaiveDate, NaiveTime, Timelike};
+use rust_xlsxwriter::{ExcelDateTime, Format, Workbook, XlsxError};
+use serde::Serialize;
+
+use crate::config::Export;
+use crate::duration_newtype::Duration;
+use crate::models::{PresenceInterval, TimeSheetPeriodRow, TimeSheetRow};
+
+/// languages which write decimal numbers with a comma
+static DECIMAL_COMMA_LANGUAGES: &[&str] = &[
+    "bg", "cs", "da", "de", "el", "es", "et", "fi", "fr", "hr", "hu", "id", "it", "lt", "lv",
+    "nb", "nl", "nn", "no", "pl", "pt", "ro", "ru", "sk", "sl", "sr", "sv", "tr", "uk",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    Tsv,
+    Xlsx,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ExportError {
+    #[error("An xlsx export has to be written to a file, use --output")]
+    MissingOutput,
+
+    #[error("Could not write export")]
+    Io(#[from] io::Error),
+
+    #[error("Could not serialize to json")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Could not write csv")]
+    Csv(#[from] csv::Error),
+
+    #[error("Could not write xlsx")]
+    Xlsx(#[from] XlsxError),
+
+    #[error("The delimiter '{0}' is not supported, use a single ascii character like ',' or ';'")]
+    InvalidDelimiter(char),
+}
+
+/// A single cell of an exported row
+pub enum ExportValue {
+    Text(String),
+    Date(NaiveDate),
+    Time(NaiveTime),
+    Duration(Duration),
+    /// a duration as decimal hours
+    Hours(Duration),
+}
+
+/// Rows which can be exported as table.
+/// Json exports use the serde representation instead.
+pub trait ExportRecord: Serialize {
+    fn headers() -> Vec<&'static str>;
+    fn values(&self) -> Vec<ExportValue>;
+}
+
+/// Writes the records in the given format to the output file or stdout
+pub fn export<T: ExportRecord>(
+    records: &[T],
+    format: ExportFormat,
+    output: &Option<PathBuf>,
+    settings: &Export) -> Result<(), ExportError>
+{
+    let decimal_separator = settings.decimal_separator.unwrap_or_else(locale_decimal_separator);
+    match format {
+        ExportFormat::Json => {
+            let mut writer = open_output(output)?;
+            serde_json::to_writer_pretty(&mut writer, records)?;
+            writeln!(writer)?;
+        }
+        ExportFormat::Csv => {
+            write_delimited(records, settings.delimiter, decimal_separator, open_output(output)?)?;
+        }
+        ExportFormat::Tsv => {
+            write_delimited(records, '\t', decimal_separator, open_output(output)?)?;
+        }
+        ExportFormat::Xlsx => {
+            let path = output.as_ref().ok_or(ExportError::MissingOutput)?;
+            write_xlsx(records, path)?;
+        }
+    }
+    Ok(())
+}
+
+fn open_output(output: &Option<PathBuf>) -> io::Result<Box<dyn Write>> {
+    Ok(match output {
+        None => { Box::new(io::stdout()) }
+        Some(path) => { Box::new(File::create(path)?) }
+    })
+}
+
+/// derives the decimal separator from the locale in the environment
+fn locale_decimal_separator() -> char {
+    let locale = ["LC_ALL", "LC_NUMERIC", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default();
+    let language = locale.split(['_', '.', '-']).next().unwrap_or_default();
+    if DECIMAL_COMMA_LANGUAGES.contains(&language) { ',' } else { '.' }
+}
+
+fn format_value(value: &ExportValue, decimal_separator: char) -> String {
+    match value {
+        ExportValue::Text(text) => { text.clone() }
+        ExportValue::Date(date) => { date.format("%Y-%m-%d").to_string() }
+        ExportValue::Time(time) if time.num_seconds_from_midnight() == 0 => { "".to_string() }
+        ExportValue::Time(time) => { time.format("%H:%M").to_string() }
+        ExportValue::Duration(duration) => { duration.format_hours_minutes() }
+        ExportValue::Hours(duration) => {
+            format!("{:.2}", duration.hours()).replace('.', &decimal_separator.to_string())
+        }
+    }
+}
+
+fn write_delimited<T: ExportRecord, W: Write>(
+    records: &[T],
+    delimiter: char,
+    decimal_separator: char,
+    writer: W) -> Result<(), ExportError>
+{
+    if !delimiter.is_ascii() {
+        return Err(ExportError::InvalidDelimiter(delimiter));
+    }
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter as u8)
+        .from_writer(writer);
+
+    writer.write_record(T::headers())?;
+    for record in records {
+        writer.write_record(record.values().iter().map(|value| format_value(value, decimal_separator)))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes an excel sheet where dates, times and hours are real numbers excel can calculate with
+fn write_xlsx<T: ExportRecord>(records: &[T], path: &Path) -> Result<(), ExportError> {
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    let bold = Format::new().set_bold();
+    let date_format = Format::new().set_num_format("yyyy-mm-dd");
+    let time_format = Format::new().set_num_format("hh:mm");
+    let hours_format = Format::new().set_num_format("0.00");
+
+    for (column, header) in T::headers().into_iter().enumerate() {
+        worksheet.write_string_with_format(0, column as u16, header, &bold)?;
+    }
+    for (row, record) in records.iter().enumerate() {
+        let row = row as u32 + 1;
+        for (column, value) in record.values().iter().enumerate() {
+            let column = column as u16;
+            match value {
+                ExportValue::Date(date) => {
+                    let date = ExcelDateTime::from_ymd(date.year() as u16, date.month() as u8, date.day() as u8)?;
+                    worksheet.write_datetime_with_format(row, column, &date, &date_format)?;
+                }
+                ExportValue::Time(time) if time.num_seconds_from_midnight() > 0 => {
+                    let time = ExcelDateTime::from_hms(time.hour() as u16, time.minute() as u8, time.second())?;
+                    worksheet.write_datetime_with_format(row, column, &time, &time_format)?;
+                }
+                ExportValue::Hours(duration) => {
+                    worksheet.write_number_with_format(row, column, duration.hours(), &hours_format)?;
+                }
+                _ => {
+                    worksheet.write_string(row, column, format_value(value, '.'))?;
+                }
+            }
+        }
+    }
+    worksheet.set_freeze_panes(1, 0)?;
+    worksheet.autofit();
+    workbook.save(path)?;
+    Ok(())
+}
+
+impl ExportRecord for TimeSheetRow {
+    fn headers() -> Vec<&'static str> {
+        vec!["Date", "Actual", "Hours", "Expected", "Delta", "Saldo", "Start", "End", "Break", "Location", "Warning", "Projects"]
+    }
+
+    fn values(&self) -> Vec<ExportValue> {
+        vec![
+            ExportValue::Date(self.date),
+            ExportValue::Duration(self.actual_duration.clone()),
+            ExportValue::Hours(self.actual_duration.clone()),
+            ExportValue::Duration(self.expected_duration.clone()),
+            ExportValue::Duration(self.delta.clone()),
+            ExportValue::Duration(self.saldo.clone()),
+            ExportValue::Time(self.start_of_business),
+            ExportValue::Time(self.end_of_business),
+            ExportValue::Duration(self.break_duration.clone()),
+            ExportValue::Text(self.locations.clone()),
+            ExportValue::Text(self.warnings.clone()),
+            ExportValue::Text(self.projects.clone()),
+        ]
+    }
+}
+
+impl ExportRecord for TimeSheetPeriodRow {
+    fn headers() -> Vec<&'static str> {
+        vec!["Period", "From", "To", "Actual", "Hours", "Expected", "Delta", "Saldo"]
+    }
+
+    fn values(&self) -> Vec<ExportValue> {
+        vec![
+            ExportValue::Text(self.period.clone()),
+            ExportValue::Date(self.start),
+            ExportValue::Date(self.end),
+            ExportValue::Duration(self.actual_duration.clone()),
+            ExportValue::Hours(self.actual_duration.clone()),
+            ExportValue::Duration(self.expected_duration.clone()),
+            ExportValue::Duration(self.delta.clone()),
+            ExportValue::Duration(self.saldo.clone()),
+        ]
+    }
+}
+
+impl ExportRecord for PresenceInterval {
+    fn headers() -> Vec<&'static str> {
+        vec!["Date", "Location", "Start", "End", "Duration", "Detections"]
+    }
+
+    fn values(&self) -> Vec<ExportValue> {
+        vec![
+            ExportValue::Date(self.date),
+            ExportValue::Text(self.location.clone()),
+            ExportValue::Time(self.start.with_timezone(&Local).time()),
+            ExportValue::Time(self.stop.with_timezone(&Local).time()),
+            ExportValue::Duration(Duration::of(self.stop - self.start)),
+            ExportValue::Text(self.detections.to_string()),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveTime};
+    use crate::duration_newtype::Duration;
+    use crate::export::{ExportError, write_delimited};
+    use crate::models::TimeSheetRow;
+
+    #[test]
+    fn test_write_delimited() {
+        let mut row = TimeSheetRow::empty(NaiveDate::from_ymd_opt(2023, 3, 10).unwrap());
+        row.actual_duration = Duration::of(chrono::Duration::minutes(8 * 60 + 45));
+        row.delta = Duration::of(chrono::Duration::minutes(-15));
+        row.start_of_business = NaiveTime::from_hms_opt(8, 30, 0).unwrap();
+        row.locations = "Office; Home".to_string();
+
+        let mut output = Vec::new();
+        write_delimited(&[row], ';', ',', &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!("Date;Actual;Hours;Expected;Delta;Saldo;Start;End;Break;Location;Warning;Projects", lines[0]);
+        assert_eq!("2023-03-10;08:45;8,75;00:00;-00:15;00:00;08:30;;00:00;\"Office; Home\";;", lines[1]);
+        assert!(matches!(write_delimited::<TimeSheetRow, _>(&[], '§', ',', Vec::new()), Err(ExportError::InvalidDelimiter('§'))));
+    }
+}
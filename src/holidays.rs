@@ -0,0 +1,245 @@
+use std::fs;
+use std::path::Path;
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use log::debug;
+
+use crate::config::Holidays;
+use crate::models::Holiday;
+
+/// all german federal states by their official abbreviation
+static GERMAN_STATES: &[&str] = &[
+    "BW", "BY", "BE", "BB", "HB", "HH", "HE", "MV", "NI", "NW", "RP", "SL", "SN", "ST", "SH", "TH",
+];
+
+#[derive(thiserror::Error, Debug)]
+pub enum HolidayError {
+    #[error("Unknown region '{0}'. Use one of the german federal states: {1}")]
+    UnknownRegion(String, String),
+
+    #[error("Could not read holiday file {0}")]
+    Io(String, #[source] std::io::Error),
+
+    #[error("Could not parse holiday file")]
+    Csv(#[from] csv::Error),
+
+    #[error("Invalid date '{0}' in holiday file")]
+    InvalidDate(String),
+}
+
+/// Loads all holidays between start and end (both inclusive) from the bundled rules
+/// and all configured holiday files.
+pub fn holidays_between(config: &Holidays, start: NaiveDate, end: NaiveDate) -> Result<Vec<Holiday>, HolidayError> {
+    let mut holidays = Vec::new();
+
+    if let Some(region) = &config.region {
+        for year in start.year()..=end.year() {
+            holidays.extend(german_holidays(region, year)?);
+        }
+    }
+
+    for path in &config.files {
+        debug!("Loading holidays from {}", path.display());
+        let loaded = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("ics") => { load_ical(path)? }
+            _ => { load_csv(path)? }
+        };
+        holidays.extend(loaded);
+    }
+
+    holidays.retain(|holiday| start <= holiday.date && holiday.date <= end);
+    holidays.sort_by_key(|holiday| holiday.date);
+    Ok(holidays)
+}
+
+/// Calculates easter sunday with the anonymous gregorian algorithm
+pub fn easter_sunday(year: i32) -> NaiveDate {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32).expect("Easter sunday is always a valid date!")
+}
+
+/// Returns the public holidays of a german federal state like "BY" or "NW" for the given year
+pub fn german_holidays(region: &str, year: i32) -> Result<Vec<Holiday>, HolidayError> {
+    let region = region.to_uppercase();
+    if !GERMAN_STATES.contains(&region.as_str()) {
+        return Err(HolidayError::UnknownRegion(region, GERMAN_STATES.join(", ")));
+    }
+    let is_in = |states: &[&str]| states.contains(&region.as_str());
+
+    let fixed = |month: u32, day: u32| NaiveDate::from_ymd_opt(year, month, day).unwrap();
+    let easter = easter_sunday(year);
+
+    let mut holidays = vec![
+        (fixed(1, 1), "Neujahr"),
+        (easter - Duration::days(2), "Karfreitag"),
+        (easter + Duration::days(1), "Ostermontag"),
+        (fixed(5, 1), "Tag der Arbeit"),
+        (easter + Duration::days(39), "Christi Himmelfahrt"),
+        (easter + Duration::days(50), "Pfingstmontag"),
+        (fixed(10, 3), "Tag der Deutschen Einheit"),
+        (fixed(12, 25), "1. Weihnachtstag"),
+        (fixed(12, 26), "2. Weihnachtstag"),
+    ];
+
+    if is_in(&["BW", "BY", "ST"]) {
+        holidays.push((fixed(1, 6), "Heilige Drei Könige"));
+    }
+    if (is_in(&["BE"]) && year >= 2019) || (is_in(&["MV"]) && year >= 2023) {
+        holidays.push((fixed(3, 8), "Internationaler Frauentag"));
+    }
+    if is_in(&["BW", "BY", "HE", "NW", "RP", "SL"]) {
+        holidays.push((easter + Duration::days(60), "Fronleichnam"));
+    }
+    if is_in(&["SL"]) {
+        holidays.push((fixed(8, 15), "Mariä Himmelfahrt"));
+    }
+    if is_in(&["TH"]) && year >= 2019 {
+        holidays.push((fixed(9, 20), "Weltkindertag"));
+    }
+    if is_in(&["BB", "MV", "SN", "ST", "TH"])
+        || (is_in(&["HB", "HH", "NI", "SH"]) && year >= 2018)
+        || year == 2017 {
+        holidays.push((fixed(10, 31), "Reformationstag"));
+    }
+    if is_in(&["BW", "BY", "NW", "RP", "SL"]) {
+        holidays.push((fixed(11, 1), "Allerheiligen"));
+    }
+    if is_in(&["SN"]) {
+        // wednesday before the 23rd of november
+        let mut day = fixed(11, 22);
+        while day.weekday() != Weekday::Wed {
+            day -= Duration::days(1);
+        }
+        holidays.push((day, "Buß- und Bettag"));
+    }
+
+    let mut holidays: Vec<Holiday> = holidays
+        .into_iter()
+        .map(|(date, name)| Holiday { date, name: name.to_string() })
+        .collect();
+    holidays.sort_by_key(|holiday| holiday.date);
+    Ok(holidays)
+}
+
+/// Loads holidays from a csv file with the columns date (YYYY-MM-DD) and name
+pub fn load_csv(path: &Path) -> Result<Vec<Holiday>, HolidayError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .comment(Some(b'#'))
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_path(path)?;
+
+    let mut holidays = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let date = record.get(0).unwrap_or_default();
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map_err(|_| HolidayError::InvalidDate(date.to_string()))?;
+        let name = record.get(1).unwrap_or("Holiday").to_string();
+        holidays.push(Holiday { date, name });
+    }
+    Ok(holidays)
+}
+
+/// Loads all all-day events of an iCalendar file as holidays
+pub fn load_ical(path: &Path) -> Result<Vec<Holiday>, HolidayError> {
+    let contents = fs::read_to_string(path)
+        .map_err(|error| HolidayError::Io(path.display().to_string(), error))?;
+    parse_ical(&contents)
+}
+
+fn parse_ical(contents: &str) -> Result<Vec<Holiday>, HolidayError> {
+    // long lines are folded by a line break followed by a space
+    let unfolded = contents
+        .replace("\r\n", "\n")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+
+    let mut holidays = Vec::new();
+    let mut start: Option<NaiveDate> = None;
+    let mut end: Option<NaiveDate> = None;
+    let mut name: Option<String> = None;
+
+    for line in unfolded.lines() {
+        let Some((key, value)) = line.split_once(':') else { continue };
+        // strip parameters like DTSTART;VALUE=DATE
+        let key = key.split(';').next().unwrap_or_default();
+
+        match key {
+            "BEGIN" if value == "VEVENT" => {
+                start = None;
+                end = None;
+                name = None;
+            }
+            "DTSTART" => { start = Some(parse_ical_date(value)?) }
+            "DTEND" => { end = Some(parse_ical_date(value)?) }
+            "SUMMARY" => { name = Some(value.replace("\\,", ",").replace("\\;", ";")) }
+            "END" if value == "VEVENT" => {
+                if let Some(start) = start {
+                    // the end date of all day events is exclusive
+                    let end = end.unwrap_or(start + Duration::days(1)).max(start + Duration::days(1));
+                    let name = name.clone().unwrap_or("Holiday".to_string());
+                    for date in start.iter_days().take_while(|date| *date < end) {
+                        holidays.push(Holiday { date, name: name.clone() });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(holidays)
+}
+
+fn parse_ical_date(value: &str) -> Result<NaiveDate, HolidayError> {
+    let date = value.get(0..8).unwrap_or(value);
+    NaiveDate::parse_from_str(date, "%Y%m%d")
+        .map_err(|_| HolidayError::InvalidDate(value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use crate::holidays::{easter_sunday, german_holidays, parse_ical};
+
+    #[test]
+    fn test_easter_sunday() {
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 4, 9).unwrap(), easter_sunday(2023));
+        assert_eq!(NaiveDate::from_ymd_opt(2024, 3, 31).unwrap(), easter_sunday(2024));
+        assert_eq!(NaiveDate::from_ymd_opt(2025, 4, 20).unwrap(), easter_sunday(2025));
+    }
+
+    #[test]
+    fn test_german_holidays() {
+        let bavaria = german_holidays("BY", 2023).unwrap();
+        assert_eq!(12, bavaria.len());
+        assert!(bavaria.iter().any(|holiday| holiday.date == NaiveDate::from_ymd_opt(2023, 6, 8).unwrap()));
+
+        let saxony = german_holidays("SN", 2023).unwrap();
+        assert!(saxony.iter().any(|holiday| holiday.date == NaiveDate::from_ymd_opt(2023, 11, 22).unwrap()));
+
+        assert!(german_holidays("XX", 2023).is_err());
+    }
+
+    #[test]
+    fn test_parse_ical() {
+        let ical = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nDTSTART;VALUE=DATE:20231224\r\nDTEND;VALUE=DATE:20231227\r\nSUMMARY:Betriebs\r\n ferien\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let holidays = parse_ical(ical).unwrap();
+        assert_eq!(3, holidays.len());
+        assert_eq!("Betriebsferien", holidays[0].name);
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 12, 26).unwrap(), holidays[2].date);
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use std::io::Read;
+
+use chrono::{DateTime, Utc};
+
+use crate::config::ColumnMapping;
+use crate::dates::{parse_date_time, parse_duration};
+use crate::models::TimeEntry;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ImportFormat {
+    /// csv with the columns configured in [import.columns]
+    Csv,
+    /// detailed csv export of toggl track
+    Toggl,
+    /// detailed csv export of clockify
+    Clockify,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ImportError {
+    #[error("Could not read csv")]
+    Csv(#[from] csv::Error),
+
+    #[error("The column '{0}' is missing")]
+    MissingColumn(String),
+
+    #[error("Line {0}: invalid {1} '{2}'")]
+    InvalidValue(u64, String, String),
+
+    #[error("Line {0}: the time entry ends before it starts")]
+    EndBeforeStart(u64),
+
+    #[error("The delimiter '{0}' is not supported, use a single ascii character like ',' or ';'")]
+    InvalidDelimiter(char),
+}
+
+/// A time entry read from a file, before it got an id
+#[derive(Debug, Clone)]
+pub struct ImportedTimeEntry {
+    /// line in the file, for error messages
+    pub line: u64,
+    /// id in the source or a key derived from start, stop and description
+    pub external_id: String,
+    pub description: Option<String>,
+    pub start: DateTime<Utc>,
+    pub stop: DateTime<Utc>,
+    pub project: Option<String>,
+    pub tags: Vec<String>,
+    pub billable: bool,
+}
+
+impl ImportFormat {
+    /// marks the imported time entries in the database
+    pub fn source(&self) -> &'static str {
+        match self {
+            ImportFormat::Csv => { "csv" }
+            ImportFormat::Toggl => { "toggl-csv" }
+            ImportFormat::Clockify => { "clockify-csv" }
+        }
+    }
+
+    pub fn mapping(&self, configured: &ColumnMapping) -> ColumnMapping {
+        let column = |name: &str| Some(name.to_string());
+        match self {
+            ImportFormat::Csv => { configured.clone() }
+            ImportFormat::Toggl => {
+                ColumnMapping {
+                    id: None,
+                    start: "Start date".to_string(),
+                    start_time: column("Start time"),
+                    end: column("End date"),
+                    end_time: column("End time"),
+                    duration: None,
+                    description: column("Description"),
+                    project: column("Project"),
+                    tags: column("Tags"),
+                    billable: column("Billable"),
+                }
+            }
+            ImportFormat::Clockify => {
+                ColumnMapping {
+                    id: None,
+                    start: "Start Date".to_string(),
+                    start_time: column("Start Time"),
+                    end: column("End Date"),
+                    end_time: column("End Time"),
+                    duration: None,
+                    description: column("Description"),
+                    project: column("Project"),
+                    tags: column("Tags"),
+                    billable: column("Billable"),
+                }
+            }
+        }
+    }
+}
+
+/// Reads all time entries from a csv file with a header line
+pub fn read_csv<R: Read>(reader: R, mapping: &ColumnMapping, delimiter: char) -> Result<Vec<ImportedTimeEntry>, ImportError> {
+    if !delimiter.is_ascii() {
+        return Err(ImportError::InvalidDelimiter(delimiter));
+    }
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter as u8)
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(reader);
+
+    let headers: HashMap<String, usize> = reader
+        .headers()?
+        .iter()
+        .enumerate()
+        .map(|(index, name)| (name.trim_start_matches('\u{feff}').to_string(), index))
+        .collect();
+    let index_of = |name: &Option<String>| -> Result<Option<usize>, ImportError> {
+        match name {
+            None => { Ok(None) }
+            Some(name) => {
+                headers.get(name).copied().map(Some).ok_or(ImportError::MissingColumn(name.clone()))
+            }
+        }
+    };
+
+    let start_column = index_of(&Some(mapping.start.clone()))?.unwrap_or_default();
+    let start_time_column = index_of(&mapping.start_time)?;
+    let end_column = index_of(&mapping.end)?;
+    let end_time_column = index_of(&mapping.end_time)?;
+    let duration_column = index_of(&mapping.duration)?;
+    let id_column = index_of(&mapping.id)?;
+    let description_column = index_of(&mapping.description)?;
+    let project_column = index_of(&mapping.project)?;
+    let tags_column = index_of(&mapping.tags)?;
+    let billable_column = index_of(&mapping.billable)?;
+    if end_column.is_none() && duration_column.is_none() {
+        return Err(ImportError::MissingColumn("end or duration".to_string()));
+    }
+
+    let mut entries = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let line = record.position().map(|position| position.line()).unwrap_or_default();
+        let get = |column: Option<usize>| column
+            .and_then(|column| record.get(column))
+            .filter(|value| !value.is_empty())
+            .map(String::from);
+
+        let parse_instant = |name: &str, date: Option<usize>, time: Option<usize>| -> Result<Option<DateTime<Utc>>, ImportError> {
+            let value = match (get(date), get(time)) {
+                (Some(date), Some(time)) => { format!("{date} {time}") }
+                (Some(date), None) => { date }
+                _ => { return Ok(None) }
+            };
+            parse_date_time(&value)
+                .map(Some)
+                .map_err(|_| ImportError::InvalidValue(line, name.to_string(), value))
+        };
+
+        let start = parse_instant("start", Some(start_column), start_time_column)?
+            .ok_or(ImportError::InvalidValue(line, "start".to_string(), "".to_string()))?;
+        let stop = match parse_instant("end", end_column, end_time_column)? {
+            Some(stop) => { stop }
+            None => {
+                let value = get(duration_column).unwrap_or_default();
+                let duration = parse_duration(&value)
+                    .ok_or(ImportError::InvalidValue(line, "duration".to_string(), value))?;
+                start + duration
+            }
+        };
+        if stop < start {
+            return Err(ImportError::EndBeforeStart(line));
+        }
+
+        let description = get(description_column);
+        let external_id = get(id_column).unwrap_or(format!(
+            "{}|{}|{}",
+            start.to_rfc3339(),
+            stop.to_rfc3339(),
+            description.clone().unwrap_or_default()
+        ));
+
+        entries.push(ImportedTimeEntry {
+            line,
+            external_id,
+            description,
+            start,
+            stop,
+            project: get(project_column),
+            tags: get(tags_column)
+                .map(|tags| tags
+                    .split(',')
+                    .map(|tag| tag.trim().to_string())
+                    .filter(|tag| !tag.is_empty())
+                    .collect())
+                .unwrap_or_default(),
+            billable: get(billable_column)
+                .map(|billable| matches!(billable.to_lowercase().as_str(), "yes" | "true" | "1"))
+                .unwrap_or(false),
+        });
+    }
+    Ok(entries)
+}
+
+/// Splits the imported time entries into the ones which can be imported
+/// and the ones overlapping an existing time entry or an earlier imported one.
+/// Running time entries are treated as if they ended now.
+pub fn split_overlapping(mut entries: Vec<ImportedTimeEntry>, existing: &[TimeEntry]) -> (Vec<ImportedTimeEntry>, Vec<ImportedTimeEntry>) {
+    let now = Utc::now();
+    entries.sort_by_key(|entry| entry.start);
+
+    let mut accepted: Vec<ImportedTimeEntry> = Vec::new();
+    let mut overlapping = Vec::new();
+    for entry in entries {
+        let overlaps_existing = existing
+            .iter()
+            .any(|time_entry| time_entry.start < entry.stop && entry.start < time_entry.stop.unwrap_or(now));
+        let overlaps_accepted = accepted
+            .iter()
+            .any(|other| other.start < entry.stop && entry.start < other.stop);
+        if overlaps_existing || overlaps_accepted {
+            overlapping.push(entry);
+        } else {
+            accepted.push(entry);
+        }
+    }
+    (accepted, overlapping)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, NaiveDate, TimeZone};
+    use crate::config::ColumnMapping;
+    use crate::import::{ImportError, ImportFormat, read_csv, split_overlapping};
+
+    #[test]
+    fn test_read_toggl_csv() {
+        let csv = "User,Email,Client,Project,Task,Description,Billable,Start date,Start time,End date,End time,Duration,Tags,Amount ()\n\
+                   Jane,jane@example.com,ACME,Rocket,,Review,Yes,2023-03-10,08:00:00,2023-03-10,10:30:00,02:30:00,\"rust, review\",\n\
+                   Jane,jane@example.com,,,,Mails,No,2023-03-10,10:00:00,2023-03-10,11:00:00,01:00:00,,\n";
+        let entries = read_csv(csv.as_bytes(), &ImportFormat::Toggl.mapping(&ColumnMapping::default()), ',').unwrap();
+
+        assert_eq!(2, entries.len());
+        let start = Local.with_ymd_and_hms(2023, 3, 10, 8, 0, 0).unwrap();
+        assert_eq!(start, entries[0].start);
+        assert_eq!(150, (entries[0].stop - entries[0].start).num_minutes());
+        assert_eq!(Some("Rocket".to_string()), entries[0].project);
+        assert_eq!(vec!["rust".to_string(), "review".to_string()], entries[0].tags);
+        assert!(entries[0].billable);
+        assert_eq!(None, entries[1].project);
+
+        let (accepted, overlapping) = split_overlapping(entries, &[]);
+        assert_eq!(1, accepted.len());
+        assert_eq!(Some("Mails".to_string()), overlapping[0].description);
+        assert_eq!(3, overlapping[0].line);
+    }
+
+    #[test]
+    fn test_read_csv_with_mapping() {
+        let mapping = ColumnMapping {
+            id: Some("Nr".to_string()),
+            start: "Datum".to_string(),
+            start_time: Some("Von".to_string()),
+            end: None,
+            end_time: None,
+            duration: Some("Stunden".to_string()),
+            description: Some("Tätigkeit".to_string()),
+            project: None,
+            tags: None,
+            billable: None,
+        };
+        let csv = "Nr;Datum;Von;Stunden;Tätigkeit\n7;2023-03-13;09:00;1,5;Workshop\n";
+        let entries = read_csv(csv.as_bytes(), &mapping, ';').unwrap();
+
+        assert_eq!("7", entries[0].external_id);
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 3, 13).unwrap(), entries[0].start.with_timezone(&Local).date_naive());
+        assert_eq!(90, (entries[0].stop - entries[0].start).num_minutes());
+
+        assert!(read_csv("Datum;Von\n".as_bytes(), &mapping, ';').is_err());
+        assert!(matches!(read_csv(csv.as_bytes(), &mapping, '§'), Err(ImportError::InvalidDelimiter('§'))));
+    }
+}
@@ -6,14 +6,13 @@
 
 
 
-use std::{process, result};
+use std::process;
 use std::process::Command;
 
-use chrono::{TimeZone};
 use clap::Parser;
 
 
-use log::{SetLoggerError, warn};
+use log::SetLoggerError;
 use rusqlite::Connection;
 
 use crate::args::{Args, EventCommand, TogglCommand};
@@ -31,6 +30,16 @@ mod toggl;
 mod commands;
 mod duration_newtype;
 mod table_cli_helper;
+mod holidays;
+mod export;
+mod printout;
+mod backup;
+mod import;
+mod report;
+mod daemon;
+mod probe;
+mod native_probe;
+mod rules;
 
 
 fn setup_logging(args: &Args) -> Result<(), SetLoggerError> {
@@ -62,7 +71,7 @@ impl<T> ErrorHandler<T> for Result<T, anyhow::Error> {
                     .unwrap_or(false);
 
                 if rust_backtrace || rust_lib_backtrace {
-                    Err::<T, anyhow::Error>(error).unwrap();
+                    panic!("{error:?}");
                 } else {
                     for cause in error.chain() {
                         println!("{cause}");
@@ -86,15 +95,24 @@ fn main() {
     let command: &Commands = &args.command.unwrap_or(Commands::Toggl {
         sub_command: TogglCommand::Show {
             compact: false,
+            offline: false,
+            group_by: None,
             start: None,
             end: None,
         },
     });
 
-    if let Commands::Clear {} = command {
-        warn!("Removing old database and creating new.");
-        config.database_path().map(std::fs::remove_file);
-        return;
+    // these commands replace or remove the database file, so they open their own connections
+    match command {
+        Commands::Clear { yes } => {
+            crate::commands::backup::execute_clear(&config, *yes).handle_error();
+            return;
+        }
+        Commands::Backup { sub_command } => {
+            crate::commands::backup::main(&config, sub_command);
+            return;
+        }
+        _ => {}
     }
 
     let mut connection = Connection::connect_database(&config).expect("Could not connect to Database!");
@@ -108,29 +126,60 @@ fn main() {
                 EventCommand::List {} => {
                     crate::commands::event::execute_list(connection);
                 }
-                EventCommand::Export {} => {
-                    crate::commands::event::execute_export(config, connection);
+                EventCommand::Timeline { range } => {
+                    crate::commands::event::execute_timeline(&config, &mut connection, range).handle_error();
+                }
+                EventCommand::Export { options } => {
+                    crate::commands::event::execute_export(config, connection, options).handle_error();
                 }
                 EventCommand::Import {} => {
                     crate::commands::event::execute_import(config, connection);
                 }
             }
         }
-        Commands::Detect {} => {
-            crate::commands::detect::main(config, connection);
+        Commands::Detect { watch } => {
+            crate::commands::detect::main(config, connection, *watch);
+        }
+        Commands::Daemon { sub_command } => {
+            crate::commands::daemon::main(config, sub_command, connection);
         }
         Commands::Probe { sub_command } => {
             crate::commands::probe::main(&mut config, sub_command);
         }
-        Commands::Clear { .. } => {}
+        Commands::Clear { .. } | Commands::Backup { .. } => {}
         Commands::Toggl { sub_command } => {
             crate::commands::toggl::main(&mut config, sub_command, &mut connection);
         }
+        Commands::Track { sub_command } => {
+            crate::commands::track::main(sub_command, &mut connection);
+        }
+        Commands::Report { by, period, format, start, end } => {
+            let (start, end) = crate::dates::parse_time_interval(start, end);
+            crate::commands::report::main(&mut connection, *by, *period, *format, start.date_naive(), end.date_naive());
+        }
+        Commands::Time { sub_command } => {
+            crate::commands::time::main(&config, sub_command, &mut connection);
+        }
+        Commands::Timesheet { sub_command } => {
+            crate::commands::timesheet::main(&config, sub_command, &mut connection);
+        }
+        Commands::Absence { sub_command } => {
+            crate::commands::absence::main(&config, sub_command, &mut connection);
+        }
+        Commands::Expected { sub_command } => {
+            crate::commands::expected::main(&config, sub_command, &mut connection);
+        }
+        Commands::Saldo { sub_command } => {
+            crate::commands::saldo::main(&config, sub_command, &mut connection);
+        }
+        Commands::Db { sub_command } => {
+            crate::commands::db::main(&config, sub_command, &mut connection);
+        }
         Commands::Config { .. } => {
             let toml = toml::to_string(&config);
             println!("{}", toml.unwrap());
         }
-        Commands::SQL { .. } => {
+        Commands::Sql { .. } => {
             let db_path = config.database_path().unwrap();
             let result = Command::new("sqlite3")
                 .arg(db_path.into_os_string())
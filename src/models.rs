@@ -1,11 +1,17 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
 use chrono::{NaiveDate, NaiveTime};
 use chrono::{DateTime, Local, Utc};
-use cli_table::{Cell, format::Justify, Table};
+use cli_table::{format::Justify, Table};
+use rusqlite::ToSql;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef};
 
 
 use serde_derive::{Deserialize, Serialize};
+use crate::config::Breaks;
+use crate::dates::Period;
 use crate::duration_newtype::Duration;
-use crate::table_cli_helper::{empty_if_time_null, cell_style_duration_unsigned, cell_style_duration_signed, cell_style_naive_date};
+use crate::table_cli_helper::{empty_if_time_null, cell_style_duration_unsigned, cell_style_duration_signed, cell_style_naive_date, cell_style_warning};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Event {
@@ -14,11 +20,80 @@ pub struct Event {
 }
 
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Holiday {
+    pub date: NaiveDate,
+    pub name: String,
+}
+
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum AbsenceKind {
+    Vacation,
+    Sick,
+    Other,
+    /// a day off taken from the overtime, it reduces the saldo
+    Compensation,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Absence {
+    pub date: NaiveDate,
+    pub kind: AbsenceKind,
+    /// 1.0 for a full day, 0.5 for a half day
+    pub fraction: f64,
+}
+
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ExpectedDuration {
     pub date: NaiveDate,
 
     pub duration: Duration,
+
+    /// 'schedule' if derived from the work week, 'manual' if set by the user
+    pub source: String,
+}
+
+
+/// A correction of the saldo like paid out overtime
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SaldoAdjustment {
+    pub id: i64,
+    pub date: NaiveDate,
+    /// added to the saldo, negative to reduce it
+    pub amount: Duration,
+    pub reason: String,
+    /// 'manual' if entered by the user, 'cap' if calculated from the carry-over cap
+    pub source: String,
+}
+
+
+/// A contiguous stay at a location, merged from repeated detections of the same event
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PresenceInterval {
+    /// the day of the timesheet the interval belongs to, which is the UTC date of its start
+    pub date: NaiveDate,
+    pub location: String,
+    /// first detection of the interval
+    pub start: DateTime<Utc>,
+    /// last detection of the interval
+    pub stop: DateTime<Utc>,
+    /// number of detections merged into the interval
+    pub detections: i64,
+}
+
+impl Display for PresenceInterval {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}–{}",
+            self.location,
+            self.start.with_timezone(&Local).format("%H:%M"),
+            self.stop.with_timezone(&Local).format("%H:%M"),
+        )
+    }
 }
 
 
@@ -30,6 +105,52 @@ pub struct TimeEntry {
     pub stop: Option<DateTime<Utc>>,
     pub project_id: Option<i64>,
     pub workspace_id: Option<i64>,
+    #[serde(default)]
+    pub billable: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// a time entry with the names of its workspace, project and client
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TimeEntryDetails {
+    #[serde(flatten)]
+    pub time_entry: TimeEntry,
+    pub workspace: Option<String>,
+    pub project: Option<String>,
+    pub client: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Workspace {
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Project {
+    pub id: i64,
+    pub workspace_id: i64,
+    pub client_id: Option<i64>,
+    pub name: String,
+    pub color: Option<String>,
+    #[serde(default)]
+    pub active: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Client {
+    pub id: i64,
+    #[serde(alias = "wid")]
+    pub workspace_id: i64,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Tag {
+    pub id: i64,
+    pub workspace_id: i64,
+    pub name: String,
 }
 
 pub(crate) type TimeSheet = Vec<TimeSheetRow>;
@@ -58,12 +179,51 @@ pub struct TimeSheetRow {
     #[table(title = "EOB", justify = "Justify::Right", customize_fn = "empty_if_time_null")]
     pub normalized_end_of_business: NaiveTime,
 
+    #[table(title = "Start", justify = "Justify::Right", customize_fn = "empty_if_time_null")]
+    pub start_of_business: NaiveTime,
+
+    #[table(title = "End", justify = "Justify::Right", customize_fn = "empty_if_time_null")]
+    pub end_of_business: NaiveTime,
+
+    #[table(title = "Break", justify = "Justify::Right", customize_fn = "cell_style_duration_unsigned")]
+    pub break_duration: Duration,
+
     #[table(title = "Location", justify = "Justify::Left")]
     pub locations: String,
+
+    #[table(title = "Warning", justify = "Justify::Left", customize_fn = "cell_style_warning")]
+    pub warnings: String,
+
+    #[table(title = "Projects", justify = "Justify::Left")]
+    pub projects: String,
+
+    /// true for a day without any data filled in for a gap, its saldo is not known
+    #[table(skip)]
+    #[serde(skip)]
+    pub filled: bool,
 }
 
 
 impl TimeSheetRow {
+    /// calculates the normalized start and end of business from the worked time
+    /// and warns about violations of the break rules
+    pub fn apply_breaks(&mut self, breaks: &Breaks) {
+        let worked = self.actual_duration.chrono_duration;
+        if worked > chrono::Duration::zero() {
+            self.normalized_start_of_business = breaks.start_of_business;
+            self.normalized_end_of_business = breaks.normalized_end_of_business(worked);
+
+            let mut warnings = Vec::new();
+            if self.break_duration.chrono_duration < breaks.required_break(worked) {
+                warnings.push(format!("break < {}m", breaks.required_break(worked).num_minutes()));
+            }
+            if worked.num_seconds() > breaks.maximum_working_seconds as i64 {
+                warnings.push(format!("worked > {}h", breaks.maximum_working_seconds / 3600));
+            }
+            self.warnings = warnings.join(", ");
+        }
+    }
+
     pub fn empty(date: NaiveDate) -> TimeSheetRow {
         TimeSheetRow {
             date,
@@ -73,8 +233,96 @@ impl TimeSheetRow {
             saldo: Duration::default(),
             normalized_start_of_business: Default::default(),
             normalized_end_of_business: Default::default(),
+            start_of_business: Default::default(),
+            end_of_business: Default::default(),
+            break_duration: Duration::default(),
             locations: "".to_string(),
+            warnings: "".to_string(),
+            projects: "".to_string(),
+            filled: false,
+        }
+    }
+
+    /// an empty row filling in a day the timesheet has no data for
+    pub fn filler(date: NaiveDate) -> TimeSheetRow {
+        TimeSheetRow { filled: true, ..TimeSheetRow::empty(date) }
+    }
+}
+
+
+/// the timesheet summed up per week, month or year
+#[derive(Debug, Serialize, Clone)]
+pub struct TimeSheetPeriodRow {
+    /// label like 2023-W14, 2023-04 or 2023
+    pub period: String,
+    /// first and last day of the timesheet within the period
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub actual_duration: Duration,
+    pub expected_duration: Duration,
+    pub delta: Duration,
+    /// saldo at the end of the period
+    pub saldo: Duration,
+    /// true if the period contains today
+    #[serde(skip)]
+    pub current: bool,
+}
+
+/// Sums up actual, expected and delta of a timesheet sorted by date per period
+/// and keeps the saldo of the last day of each period.
+pub fn rollup(timesheet: &TimeSheet, period: Period, today: NaiveDate) -> Vec<TimeSheetPeriodRow> {
+    let mut rows: Vec<TimeSheetPeriodRow> = Vec::new();
+    let mut saldo = Duration::default();
+
+    for row in timesheet {
+        let start = period.start_of(row.date);
+        match rows.last_mut() {
+            Some(last) if period.start_of(last.start) == start => {
+                last.end = row.date;
+                last.actual_duration.chrono_duration = last.actual_duration.chrono_duration + row.actual_duration.chrono_duration;
+                last.expected_duration.chrono_duration = last.expected_duration.chrono_duration + row.expected_duration.chrono_duration;
+                last.delta.chrono_duration = last.delta.chrono_duration + row.delta.chrono_duration;
+            }
+            _ => {
+                rows.push(TimeSheetPeriodRow {
+                    period: period.label(row.date),
+                    start: row.date,
+                    end: row.date,
+                    actual_duration: row.actual_duration.clone(),
+                    expected_duration: row.expected_duration.clone(),
+                    delta: row.delta.clone(),
+                    saldo: saldo.clone(),
+                    current: start == period.start_of(today),
+                });
+            }
+        }
+
+        // days filled in for gaps carry no saldo, the one of the previous day still holds
+        if !row.filled {
+            saldo = row.saldo.clone();
         }
+        if let Some(last) = rows.last_mut() {
+            last.saldo = saldo.clone();
+        }
+    }
+    rows
+}
+
+impl From<&TimeSheetPeriodRow> for Vec<cli_table::CellStruct> {
+    fn from(row: &TimeSheetPeriodRow) -> Vec<cli_table::CellStruct> {
+        use cli_table::{Cell, Style};
+        vec![
+            row.period.clone().cell(),
+            row.start.cell(),
+            row.end.cell(),
+            cell_style_duration_unsigned("".cell(), &row.actual_duration).justify(Justify::Right),
+            cell_style_duration_unsigned("".cell(), &row.expected_duration).justify(Justify::Right),
+            cell_style_duration_signed("".cell(), &row.delta).justify(Justify::Right),
+            cell_style_duration_signed("".cell(), &row.saldo).justify(Justify::Right),
+        ]
+            .into_iter()
+            .map(|cell| cell.bold(row.current))
+            .collect()
     }
 }
 
@@ -88,6 +336,99 @@ impl TimeEntry {
 }
 
 
+impl AbsenceKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AbsenceKind::Vacation => { "vacation" }
+            AbsenceKind::Sick => { "sick" }
+            AbsenceKind::Other => { "other" }
+            AbsenceKind::Compensation => { "compensation" }
+        }
+    }
+}
+
+impl Display for AbsenceKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromSql for AbsenceKind {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "vacation" => { Ok(AbsenceKind::Vacation) }
+            "sick" => { Ok(AbsenceKind::Sick) }
+            "other" => { Ok(AbsenceKind::Other) }
+            "compensation" => { Ok(AbsenceKind::Compensation) }
+            _ => { Err(FromSqlError::InvalidType) }
+        }
+    }
+}
+
+impl ToSql for AbsenceKind {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl From<&Absence> for Vec<cli_table::CellStruct> {
+    fn from(absence: &Absence) -> Vec<cli_table::CellStruct> {
+        use cli_table::Cell;
+        vec![
+            absence.date.cell(),
+            absence.kind.cell(),
+            if absence.fraction < 1.0 { "half day" } else { "full day" }.cell(),
+        ]
+    }
+}
+
+
+impl From<&ExpectedDuration> for Vec<cli_table::CellStruct> {
+    fn from(expected_duration: &ExpectedDuration) -> Vec<cli_table::CellStruct> {
+        use cli_table::Cell;
+        vec![
+            expected_duration.date.cell(),
+            expected_duration.date.format("%a").cell(),
+            expected_duration.duration.format_hours_minutes().cell(),
+            expected_duration.source.clone().cell(),
+        ]
+    }
+}
+
+
+impl From<&SaldoAdjustment> for Vec<cli_table::CellStruct> {
+    fn from(adjustment: &SaldoAdjustment) -> Vec<cli_table::CellStruct> {
+        use cli_table::Cell;
+        vec![
+            adjustment.id.cell(),
+            adjustment.date.cell(),
+            adjustment.amount.format_hours_minutes().cell().justify(cli_table::format::Justify::Right),
+            adjustment.reason.clone().cell(),
+            adjustment.source.clone().cell(),
+        ]
+    }
+}
+
+
+impl From<&TimeEntryDetails> for Vec<cli_table::CellStruct> {
+    fn from(details: &TimeEntryDetails) -> Vec<cli_table::CellStruct> {
+        use cli_table::Cell;
+        let time_entry = &details.time_entry;
+        vec![
+            time_entry.start.with_timezone(&Local).format("%Y-%m-%d %H:%M").cell(),
+            time_entry.stop.map(|stop| stop.with_timezone(&Local).format("%H:%M").to_string()).unwrap_or_default().cell(),
+            time_entry.duration().unwrap_or_default().format_unsigned().cell().justify(Justify::Right),
+            details.workspace.clone().unwrap_or_default().cell(),
+            details.client.clone().unwrap_or_default().cell(),
+            details.project.clone().unwrap_or_default().cell(),
+            time_entry.description.clone().unwrap_or_default().cell(),
+            time_entry.tags.join(", ").cell(),
+            if time_entry.billable { "yes" } else { "" }.cell(),
+        ]
+    }
+}
+
+
 impl From<&Event> for Vec<cli_table::CellStruct> {
     fn from(office_location: &Event) -> Vec<cli_table::CellStruct> {
         use cli_table::Cell;
@@ -103,3 +444,59 @@ impl From<&Event> for Vec<cli_table::CellStruct> {
 
 
 
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use crate::dates::Period;
+    use crate::duration_newtype::Duration;
+    use crate::models::{rollup, TimeSheetRow};
+
+    fn row(day: u32, actual_hours: i64, expected_hours: i64, saldo_hours: i64) -> TimeSheetRow {
+        let mut row = TimeSheetRow::empty(NaiveDate::from_ymd_opt(2023, 3, day).unwrap());
+        row.actual_duration = Duration::of(chrono::Duration::hours(actual_hours));
+        row.expected_duration = Duration::of(chrono::Duration::hours(expected_hours));
+        row.delta = Duration::of(chrono::Duration::hours(actual_hours - expected_hours));
+        row.saldo = Duration::of(chrono::Duration::hours(saldo_hours));
+        row
+    }
+
+    fn filler(day: u32) -> TimeSheetRow {
+        TimeSheetRow::filler(NaiveDate::from_ymd_opt(2023, 3, day).unwrap())
+    }
+
+    #[test]
+    fn test_rollup() {
+        let timesheet = vec![
+            row(10, 9, 8, 1),
+            filler(11),
+            filler(12),
+            row(13, 6, 8, -1),
+            row(14, 8, 8, -1),
+            filler(20),
+            // a real day can bring the saldo to exactly zero, eg. with an adjustment
+            row(27, 0, 0, 0),
+        ];
+        let today = NaiveDate::from_ymd_opt(2023, 3, 14).unwrap();
+
+        let weeks = rollup(&timesheet, Period::Week, today);
+        assert_eq!(4, weeks.len());
+        assert_eq!("2023-W10", weeks[0].period);
+        assert_eq!(1, weeks[0].saldo.chrono_duration.num_hours());
+        assert!(!weeks[0].current);
+        assert_eq!(14, weeks[1].actual_duration.chrono_duration.num_hours());
+        assert_eq!(-2, weeks[1].delta.chrono_duration.num_hours());
+        assert_eq!(-1, weeks[1].saldo.chrono_duration.num_hours());
+        assert!(weeks[1].current);
+        // an empty day keeps the saldo of the days before
+        assert_eq!(-1, weeks[2].saldo.chrono_duration.num_hours());
+        assert_eq!(0, weeks[3].saldo.chrono_duration.num_hours());
+
+        let months = rollup(&timesheet, Period::Month, today);
+        assert_eq!(1, months.len());
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 3, 27).unwrap(), months[0].end);
+        assert_eq!(0, months[0].saldo.chrono_duration.num_hours());
+        assert_eq!(-1, months[0].delta.chrono_duration.num_hours());
+    }
+}
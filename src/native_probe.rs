@@ -0,0 +1,253 @@
+use std::fs;
+use std::net::{Ipv4Addr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::process::Command;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::{GatewayMatch, ProbeKind, WifiMatch};
+use crate::probe::{CommandStatus, run_command};
+
+/// A wifi network as listed by nmcli
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Wifi {
+    pub active: bool,
+    pub ssid: String,
+    pub bssid: String,
+}
+
+/// Checks the probe and returns if it was detected and why.
+/// Errors explain why the probe could not be checked at all.
+pub fn check(kind: &ProbeKind, timeout: Duration) -> Result<(bool, String), String> {
+    match kind {
+        ProbeKind::Command { .. } => { Err("commands are run by the probe module".to_string()) }
+        ProbeKind::Wifi { wifi } => { check_wifi(wifi, timeout) }
+        ProbeKind::Gateway { gateway } => { check_gateway(gateway) }
+        ProbeKind::Interface { interface } => { Ok(check_interface(Path::new("/sys/class/net"), interface)) }
+        ProbeKind::Tcp { tcp } => { check_tcp(tcp, timeout) }
+        ProbeKind::DnsSearch { dns_search } => {
+            let resolv_conf = read("/etc/resolv.conf")?;
+            let domains = parse_search_domains(&resolv_conf);
+            let found = domains.iter().any(|domain| domain.eq_ignore_ascii_case(dns_search));
+            Ok((found, format!("the search domains are [{}]", domains.join(", "))))
+        }
+        ProbeKind::Usb { usb } => { Ok(check_usb(Path::new("/sys/bus/usb/devices"), usb)) }
+    }
+}
+
+fn read(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|error| format!("could not read {path}: {error}"))
+}
+
+fn check_wifi(wifi: &WifiMatch, timeout: Duration) -> Result<(bool, String), String> {
+    let (status, stdout, stderr) = run_command(
+        Command::new("nmcli").args(["-t", "-f", "ACTIVE,SSID,BSSID", "device", "wifi", "list", "--rescan", "no"]),
+        timeout,
+    );
+    match status {
+        CommandStatus::Exited(Some(0)) => {}
+        CommandStatus::Exited(_) => { return Err(format!("nmcli failed: {}", stderr.trim())); }
+        CommandStatus::TimedOut => { return Err("nmcli timed out".to_string()); }
+        CommandStatus::Failed(error) => { return Err(format!("could not run nmcli: {error}")); }
+    }
+
+    let active: Vec<Wifi> = parse_nmcli_wifi(&stdout).into_iter().filter(|wifi| wifi.active).collect();
+    let found = active.iter().any(|connected| {
+        wifi.ssid.as_ref().map(|ssid| *ssid == connected.ssid).unwrap_or(true)
+            && wifi.bssid.as_ref().map(|bssid| bssid.eq_ignore_ascii_case(&connected.bssid)).unwrap_or(true)
+    });
+    let reason = match active.as_slice() {
+        [] => { "no wifi is connected".to_string() }
+        connected => {
+            let names: Vec<String> = connected.iter().map(|wifi| format!("{} ({})", wifi.ssid, wifi.bssid)).collect();
+            format!("the connected wifi is {}", names.join(", "))
+        }
+    };
+    Ok((found, reason))
+}
+
+/// Parses the terse output of nmcli -t -f ACTIVE,SSID,BSSID, where colons within values are escaped
+pub fn parse_nmcli_wifi(output: &str) -> Vec<Wifi> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = vec![String::new()];
+            let mut characters = line.chars();
+            while let Some(character) = characters.next() {
+                match character {
+                    '\\' => { fields.last_mut()?.extend(characters.next()); }
+                    ':' => { fields.push(String::new()); }
+                    _ => { fields.last_mut()?.push(character); }
+                }
+            }
+            match fields.as_slice() {
+                [active, ssid, bssid] => {
+                    Some(Wifi { active: active == "yes", ssid: ssid.clone(), bssid: bssid.clone() })
+                }
+                _ => { None }
+            }
+        })
+        .collect()
+}
+
+fn check_gateway(gateway: &GatewayMatch) -> Result<(bool, String), String> {
+    let ip = parse_default_gateway(&read("/proc/net/route")?);
+    let ip = match ip {
+        None => { return Ok((false, "there is no default gateway".to_string())); }
+        Some(ip) => { ip }
+    };
+    let mac = parse_arp_table(&read("/proc/net/arp")?)
+        .into_iter()
+        .find(|(address, _)| *address == ip)
+        .map(|(_, mac)| mac);
+
+    let found = gateway.ip.map(|expected| expected == ip).unwrap_or(true)
+        && gateway.mac.as_ref().map(|expected| mac.as_ref().map(|mac| expected.eq_ignore_ascii_case(mac)).unwrap_or(false)).unwrap_or(true);
+    Ok((found, format!("the default gateway is {ip} with mac {}", mac.unwrap_or("unknown".to_string()))))
+}
+
+/// Returns the gateway of the default route from /proc/net/route
+pub fn parse_default_gateway(route: &str) -> Option<Ipv4Addr> {
+    route
+        .lines()
+        .skip(1)
+        .map(|line| line.split_whitespace().collect::<Vec<&str>>())
+        .find(|columns| columns.get(1) == Some(&"00000000"))
+        .and_then(|columns| u32::from_str_radix(columns.get(2)?, 16).ok())
+        // the kernel prints the address in the byte order of the host
+        .map(|gateway| Ipv4Addr::from(gateway.to_ne_bytes()))
+}
+
+/// Returns the ip and mac addresses from /proc/net/arp
+pub fn parse_arp_table(arp: &str) -> Vec<(Ipv4Addr, String)> {
+    arp
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            Some((columns.first()?.parse().ok()?, columns.get(3)?.to_string()))
+        })
+        .collect()
+}
+
+fn check_interface(interfaces: &Path, interface: &str) -> (bool, String) {
+    match fs::read_to_string(interfaces.join(interface).join("operstate")) {
+        Err(_) => { (false, format!("there is no interface {interface}")) }
+        // tunnels do not report their state, so only down counts as missing
+        Ok(state) => { (state.trim() != "down", format!("the interface {interface} is {}", state.trim())) }
+    }
+}
+
+fn check_tcp(address: &str, timeout: Duration) -> Result<(bool, String), String> {
+    let deadline = Instant::now() + timeout;
+    let addresses = resolve(address, timeout)?;
+    for socket_address in addresses {
+        // the time spent resolving and on unreachable addresses is taken from the timeout
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok((false, format!("could not connect to {address} in time")));
+        }
+        if TcpStream::connect_timeout(&socket_address, remaining).is_ok() {
+            return Ok((true, format!("connected to {socket_address}")));
+        }
+    }
+    Ok((false, format!("could not connect to {address}")))
+}
+
+/// resolves the address on a helper thread, because getaddrinfo cannot be cancelled.
+/// A resolver that does not answer in time is left behind
+fn resolve(address: &str, timeout: Duration) -> Result<Vec<SocketAddr>, String> {
+    let (sender, receiver) = mpsc::channel();
+    let host = address.to_string();
+    thread::spawn(move || {
+        let _ = sender.send(host.to_socket_addrs().map(|addresses| addresses.collect::<Vec<SocketAddr>>()));
+    });
+    match receiver.recv_timeout(timeout) {
+        Ok(Ok(addresses)) => { Ok(addresses) }
+        Ok(Err(error)) => { Err(format!("could not resolve {address}: {error}")) }
+        Err(_) => { Err(format!("could not resolve {address} in time")) }
+    }
+}
+
+/// Returns the search domains from resolv.conf, the last search or domain line wins
+pub fn parse_search_domains(resolv_conf: &str) -> Vec<String> {
+    resolv_conf
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<&str>>())
+        .rfind(|columns| matches!(columns.first(), Some(&"search") | Some(&"domain")))
+        .map(|columns| columns[1..].iter().map(|domain| domain.to_string()).collect())
+        .unwrap_or_default()
+}
+
+fn check_usb(devices: &Path, id: &str) -> (bool, String) {
+    let read_id = |device: &Path, name: &str| fs::read_to_string(device.join(name)).ok().map(|id| id.trim().to_lowercase());
+    let connected: Vec<String> = fs::read_dir(devices)
+        .map(|entries| entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| Some(format!("{}:{}", read_id(&entry.path(), "idVendor")?, read_id(&entry.path(), "idProduct")?)))
+            .collect())
+        .unwrap_or_default();
+
+    let found = connected.contains(&id.to_lowercase());
+    (found, format!("{} usb devices are connected", connected.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::net::{Ipv4Addr, TcpListener};
+    use std::time::Duration;
+    use crate::native_probe::{check_interface, check_tcp, check_usb, parse_arp_table, parse_default_gateway, parse_nmcli_wifi, parse_search_domains, Wifi};
+
+    #[test]
+    fn test_parse_system_files() {
+        // written like the kernel does on this host
+        let gateway = format!("{:08X}", u32::from_ne_bytes([192, 168, 178, 1]));
+        let route = format!("Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+                     eth0\t000200C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0\n\
+                     eth0\t00000000\t{gateway}\t0003\t0\t0\t0\t00000000\t0\t0\t0\n");
+        assert_eq!(Some(Ipv4Addr::new(192, 168, 178, 1)), parse_default_gateway(&route));
+
+        let arp = "IP address       HW type     Flags       HW address            Mask     Device\n\
+                   192.168.178.1    0x1         0x2         3c:a6:2f:00:00:01     *        eth0\n";
+        assert_eq!(vec![(Ipv4Addr::new(192, 168, 178, 1), "3c:a6:2f:00:00:01".to_string())], parse_arp_table(arp));
+
+        let resolv_conf = "# comment\ndomain home\nnameserver 10.0.0.1\nsearch corp.example.com example.com\n";
+        assert_eq!(vec!["corp.example.com", "example.com"], parse_search_domains(resolv_conf));
+
+        let nmcli = "no:Guest:AA\\:BB\\:CC\\:DD\\:EE\\:01\nyes:ACME\\: Office:AA\\:BB\\:CC\\:DD\\:EE\\:FF\n";
+        assert_eq!(Wifi { active: true, ssid: "ACME: Office".to_string(), bssid: "AA:BB:CC:DD:EE:FF".to_string() }, parse_nmcli_wifi(nmcli)[1]);
+    }
+
+    #[test]
+    fn test_sysfs_probes() {
+        let sysfs = std::env::temp_dir().join(format!("papierkram-test-sysfs-{}", std::process::id()));
+        fs::create_dir_all(sysfs.join("net/tun0")).unwrap();
+        fs::create_dir_all(sysfs.join("net/wlan0")).unwrap();
+        fs::create_dir_all(sysfs.join("usb/1-2")).unwrap();
+        fs::write(sysfs.join("net/tun0/operstate"), "unknown\n").unwrap();
+        fs::write(sysfs.join("net/wlan0/operstate"), "down\n").unwrap();
+        fs::write(sysfs.join("usb/1-2/idVendor"), "17ef\n").unwrap();
+        fs::write(sysfs.join("usb/1-2/idProduct"), "3082\n").unwrap();
+
+        assert!(check_interface(&sysfs.join("net"), "tun0").0);
+        assert!(!check_interface(&sysfs.join("net"), "wlan0").0);
+        assert!(!check_interface(&sysfs.join("net"), "eth1").0);
+        assert!(check_usb(&sysfs.join("usb"), "17EF:3082").0);
+        assert!(!check_usb(&sysfs.join("usb"), "17ef:0000").0);
+
+        fs::remove_dir_all(sysfs).unwrap();
+    }
+
+    #[test]
+    fn test_tcp_probe() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        assert!(check_tcp(&address, Duration::from_secs(1)).unwrap().0);
+
+        drop(listener);
+        assert!(!check_tcp(&address, Duration::from_secs(1)).unwrap().0);
+        assert!(check_tcp("localhost", Duration::from_secs(1)).is_err());
+    }
+}
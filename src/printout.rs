@@ -0,0 +1,230 @@
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+
+use chrono::NaiveDate;
+use log::debug;
+
+use crate::dates::Period;
+use crate::models::{Absence, Holiday, rollup, TimeSheet, TimeSheetRow};
+
+/// template used if there is none in the config directory
+static DEFAULT_TEMPLATE: &str = include_str!("templates/timesheet.html");
+
+/// pdf converters used in this order if no pdf_command is configured
+static PDF_CONVERTERS: &[&str] = &[
+    "wkhtmltopdf --quiet {input} {output}",
+    "chromium --headless --no-pdf-header-footer --print-to-pdf={output} {input}",
+    "chromium-browser --headless --no-pdf-header-footer --print-to-pdf={output} {input}",
+    "google-chrome --headless --no-pdf-header-footer --print-to-pdf={output} {input}",
+];
+
+/// the part of the template between these markers is repeated for every day
+const DAYS_START: &str = "{{#days}}";
+const DAYS_END: &str = "{{/days}}";
+
+#[derive(thiserror::Error, Debug)]
+pub enum PrintError {
+    #[error("Could not read template {0}")]
+    Template(String, #[source] io::Error),
+
+    #[error("Could not run pdf command '{0}'")]
+    PdfCommand(String, #[source] io::Error),
+
+    #[error("The pdf command '{0}' failed with {1}")]
+    PdfFailed(String, ExitStatus),
+
+    #[error("No pdf was written: there is no pdf_command in the [print] section of the configuration \
+             and neither wkhtmltopdf nor chromium is in the PATH. Use --no-pdf to only write the html")]
+    NoPdfCommand,
+}
+
+/// Loads the template from the given path if it exists, the bundled template otherwise
+pub fn load_template(path: Option<&Path>) -> Result<String, PrintError> {
+    match path {
+        Some(path) if path.exists() => {
+            debug!("Using template {}", path.display());
+            fs::read_to_string(path)
+                .map_err(|error| PrintError::Template(path.display().to_string(), error))
+        }
+        _ => { Ok(DEFAULT_TEMPLATE.to_string()) }
+    }
+}
+
+/// Renders the working time record of the month containing the given date.
+/// Days missing in the timesheet are printed as empty rows.
+pub fn render_month(
+    template: &str,
+    employee: &str,
+    month: NaiveDate,
+    timesheet: &TimeSheet,
+    holidays: &[Holiday],
+    absences: &[Absence]) -> String
+{
+    let start = Period::Month.start_of(month);
+    let end = Period::Month.end_of(month);
+    let days: TimeSheet = start
+        .iter_days()
+        .take_while(|date| *date <= end)
+        .map(|date| timesheet
+            .iter()
+            .find(|row| row.date == date)
+            .cloned()
+            .unwrap_or(TimeSheetRow::filler(date)))
+        .collect();
+
+    let day_values: Vec<Vec<(&str, String)>> = days
+        .iter()
+        .map(|row| {
+            let notes: Vec<String> = holidays
+                .iter()
+                .filter(|holiday| holiday.date == row.date)
+                .map(|holiday| holiday.name.clone())
+                .chain(absences
+                    .iter()
+                    .filter(|absence| absence.date == row.date)
+                    .map(|absence| if absence.fraction < 1.0 {
+                        format!("{} (half day)", absence.kind)
+                    } else {
+                        absence.kind.to_string()
+                    }))
+                .collect();
+            let worked = !row.actual_duration.chrono_duration.is_zero();
+            let expected = !row.expected_duration.chrono_duration.is_zero();
+
+            vec![
+                ("class", if expected { "" } else { "free" }.to_string()),
+                ("date", row.date.format("%Y-%m-%d").to_string()),
+                ("weekday", row.date.format("%a").to_string()),
+                ("start", if worked { row.start_of_business.format("%H:%M").to_string() } else { "".to_string() }),
+                ("end", if worked { row.end_of_business.format("%H:%M").to_string() } else { "".to_string() }),
+                ("break", if worked { row.break_duration.format_hours_minutes() } else { "".to_string() }),
+                ("actual", if worked { row.actual_duration.format_hours_minutes() } else { "".to_string() }),
+                ("expected", if expected { row.expected_duration.format_hours_minutes() } else { "".to_string() }),
+                ("delta", if worked || expected { row.delta.format_hours_minutes() } else { "".to_string() }),
+                ("note", notes.join(", ")),
+            ]
+        })
+        .collect();
+
+    let mut values = vec![
+        ("employee", employee.to_string()),
+        ("month", start.format("%B %Y").to_string()),
+    ];
+    if let Some(total) = rollup(&days, Period::Month, start).first() {
+        values.extend([
+            ("actual", total.actual_duration.format_hours_minutes()),
+            ("expected", total.expected_duration.format_hours_minutes()),
+            ("delta", total.delta.format_hours_minutes()),
+            ("saldo", total.saldo.format_hours_minutes()),
+        ]);
+    }
+    render(template, &values, &day_values)
+}
+
+/// Returns the command of the first known pdf converter found in the given PATH
+pub fn find_pdf_command(path: &OsStr) -> Option<String> {
+    PDF_CONVERTERS
+        .iter()
+        .find(|command| {
+            let program = command.split_whitespace().next().unwrap_or_default();
+            env::split_paths(path).any(|directory| directory.join(program).is_file())
+        })
+        .map(|command| command.to_string())
+}
+
+/// Converts the html file to pdf with the configured command.
+/// The placeholders {input} and {output} in the command are replaced by the file paths.
+pub fn convert_to_pdf(command: &str, input: &Path, output: &Path) -> Result<(), PrintError> {
+    let mut arguments = command
+        .split_whitespace()
+        .map(|argument| argument
+            .replace("{input}", &input.display().to_string())
+            .replace("{output}", &output.display().to_string()));
+    let program = arguments.next().unwrap_or_default();
+    debug!("Running {command}");
+
+    let status = Command::new(program)
+        .args(arguments)
+        .status()
+        .map_err(|error| PrintError::PdfCommand(command.to_string(), error))?;
+    if !status.success() {
+        return Err(PrintError::PdfFailed(command.to_string(), status));
+    }
+    Ok(())
+}
+
+fn render(template: &str, values: &[(&str, String)], days: &[Vec<(&str, String)>]) -> String {
+    let (head, day_template, tail) = match (template.find(DAYS_START), template.find(DAYS_END)) {
+        (Some(start), Some(end)) if start < end => {
+            (&template[..start], &template[start + DAYS_START.len()..end], &template[end + DAYS_END.len()..])
+        }
+        _ => { (template, "", "") }
+    };
+
+    let mut html = fill(head, values);
+    for day in days {
+        html.push_str(&fill(day_template, day));
+    }
+    html.push_str(&fill(tail, values));
+    html
+}
+
+/// replaces every {{name}} with the html escaped value
+fn fill(template: &str, values: &[(&str, String)]) -> String {
+    values.iter().fold(template.to_string(), |html, (name, value)| {
+        html.replace(&format!("{{{{{name}}}}}"), &escape_html(value))
+    })
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use crate::duration_newtype::Duration;
+    use crate::models::{Absence, AbsenceKind, Holiday, TimeSheetRow};
+    use crate::printout::{find_pdf_command, render_month};
+
+    #[test]
+    fn test_render_month() {
+        let template = "<h1>{{employee}} {{month}}</h1>{{#days}}<tr class=\"{{class}}\"><td>{{date}}</td><td>{{actual}}</td><td>{{note}}</td></tr>\n{{/days}}<p>{{actual}} {{saldo}}</p>";
+        let mut row = TimeSheetRow::empty(NaiveDate::from_ymd_opt(2023, 4, 3).unwrap());
+        row.actual_duration = Duration::of(chrono::Duration::minutes(8 * 60 + 30));
+        row.expected_duration = Duration::of(chrono::Duration::hours(8));
+        row.saldo = Duration::of(chrono::Duration::minutes(90));
+        let holidays = vec![Holiday { date: NaiveDate::from_ymd_opt(2023, 4, 7).unwrap(), name: "Karfreitag".to_string() }];
+        let absences = vec![Absence { date: NaiveDate::from_ymd_opt(2023, 4, 4).unwrap(), kind: AbsenceKind::Vacation, fraction: 0.5 }];
+
+        let html = render_month(template, "Jane <Doe>", NaiveDate::from_ymd_opt(2023, 4, 17).unwrap(), &vec![row], &holidays, &absences);
+
+        assert!(html.starts_with("<h1>Jane &lt;Doe&gt; April 2023</h1>"));
+        assert_eq!(30, html.matches("<tr").count());
+        assert!(html.contains("<tr class=\"\"><td>2023-04-03</td><td>08:30</td><td></td></tr>"));
+        assert!(html.contains("<td>2023-04-04</td><td></td><td>vacation (half day)</td>"));
+        assert!(html.contains("<td>2023-04-07</td><td></td><td>Karfreitag</td>"));
+        assert!(html.ends_with("<p>08:30 01:30</p>"));
+    }
+
+    #[test]
+    fn test_find_pdf_command() {
+        let bin = std::env::temp_dir().join(format!("papierkram-test-bin-{}", std::process::id()));
+        std::fs::create_dir_all(&bin).unwrap();
+        assert_eq!(None, find_pdf_command(bin.as_os_str()));
+
+        std::fs::write(bin.join("chromium"), "").unwrap();
+        let command = find_pdf_command(bin.as_os_str()).unwrap();
+        assert!(command.starts_with("chromium --headless"));
+
+        std::fs::remove_dir_all(bin).unwrap();
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::io::Read;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::debug;
+
+use crate::config::{Probe, ProbeKind};
+use crate::native_probe;
+
+/// timeout of probes which do not configure one
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// how often a running command is checked for completion
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProbeStatus {
+    Detected,
+    NotDetected,
+    /// the probe was stopped after the timeout
+    TimedOut,
+    /// the probe could not be checked at all
+    Failed(String),
+}
+
+/// The result of running a single probe
+#[derive(Debug, Clone)]
+pub struct ProbeOutcome {
+    pub name: String,
+    pub status: ProbeStatus,
+    /// why the probe was detected or not
+    pub reason: String,
+    pub stdout: String,
+    pub stderr: String,
+    pub elapsed: Duration,
+}
+
+impl ProbeOutcome {
+    pub fn detected(&self) -> bool {
+        self.status == ProbeStatus::Detected
+    }
+}
+
+impl Display for ProbeOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let elapsed = self.elapsed.as_secs_f64();
+        match &self.status {
+            ProbeStatus::Detected => {
+                write!(f, "{} was detected, {} ({elapsed:.2}s)", self.name, self.reason)
+            }
+            ProbeStatus::NotDetected => {
+                write!(f, "{} was not detected, {} ({elapsed:.2}s)", self.name, self.reason)
+            }
+            ProbeStatus::TimedOut => {
+                write!(f, "{} was not detected, it timed out after {elapsed:.2}s", self.name)
+            }
+            ProbeStatus::Failed(error) => {
+                write!(f, "{} was not detected, it could not be checked: {error}", self.name)
+            }
+        }
+    }
+}
+
+impl Display for ProbeKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ProbeKind::Command { command } => { write!(f, "command {command}") }
+            ProbeKind::Wifi { wifi } => {
+                write!(f, "wifi ssid {} bssid {}",
+                       wifi.ssid.as_deref().unwrap_or("any"),
+                       wifi.bssid.as_deref().unwrap_or("any"))
+            }
+            ProbeKind::Gateway { gateway } => {
+                write!(f, "gateway ip {} mac {}",
+                       gateway.ip.map(|ip| ip.to_string()).unwrap_or("any".to_string()),
+                       gateway.mac.as_deref().unwrap_or("any"))
+            }
+            ProbeKind::Interface { interface } => { write!(f, "interface {interface}") }
+            ProbeKind::Tcp { tcp } => { write!(f, "tcp connection to {tcp}") }
+            ProbeKind::DnsSearch { dns_search } => { write!(f, "dns search domain {dns_search}") }
+            ProbeKind::Usb { usb } => { write!(f, "usb device {usb}") }
+        }
+    }
+}
+
+/// How a command ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandStatus {
+    /// the exit code, None if the command was killed by a signal
+    Exited(Option<i32>),
+    TimedOut,
+    Failed(String),
+}
+
+/// Runs all probes at the same time and returns their outcomes sorted by name
+pub fn run_probes(probes: &HashMap<String, Probe>) -> Vec<ProbeOutcome> {
+    let mut outcomes: Vec<ProbeOutcome> = thread::scope(|scope| {
+        let handles: Vec<_> = probes
+            .iter()
+            .map(|(name, probe)| scope.spawn(move || run_probe(name, probe)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("A probe thread panicked"))
+            .collect()
+    });
+    outcomes.sort_by(|a, b| a.name.cmp(&b.name));
+    outcomes
+}
+
+/// Runs a single probe and stops it after its timeout
+pub fn run_probe(name: &str, probe: &Probe) -> ProbeOutcome {
+    let timeout = probe.timeout_seconds.map(Duration::from_secs).unwrap_or(DEFAULT_TIMEOUT);
+    debug!("Running {name}: {}", probe.kind);
+    let start = Instant::now();
+
+    let (status, reason, stdout, stderr) = match &probe.kind {
+        ProbeKind::Command { command } => {
+            let (status, stdout, stderr) = run_command(Command::new("sh").arg("-c").arg(command), timeout);
+            let (status, reason) = match status {
+                CommandStatus::Exited(Some(0)) => { (ProbeStatus::Detected, "the command exited with 0".to_string()) }
+                CommandStatus::Exited(Some(code)) => { (ProbeStatus::NotDetected, format!("the command exited with {code}")) }
+                CommandStatus::Exited(None) => { (ProbeStatus::NotDetected, "the command was killed by a signal".to_string()) }
+                CommandStatus::TimedOut => { (ProbeStatus::TimedOut, String::new()) }
+                CommandStatus::Failed(error) => { (ProbeStatus::Failed(error), String::new()) }
+            };
+            (status, reason, stdout, stderr)
+        }
+        kind => {
+            let (status, reason) = match native_probe::check(kind, timeout) {
+                Ok((true, reason)) => { (ProbeStatus::Detected, reason) }
+                Ok((false, reason)) => { (ProbeStatus::NotDetected, reason) }
+                Err(error) => { (ProbeStatus::Failed(error), String::new()) }
+            };
+            (status, reason, String::new(), String::new())
+        }
+    };
+
+    let outcome = ProbeOutcome {
+        name: name.to_string(),
+        status,
+        reason,
+        stdout,
+        stderr,
+        elapsed: start.elapsed(),
+    };
+    debug!("{outcome}");
+    if !outcome.stdout.is_empty() || !outcome.stderr.is_empty() {
+        debug!("{name} stdout: {}", outcome.stdout.trim_end());
+        debug!("{name} stderr: {}", outcome.stderr.trim_end());
+    }
+    outcome
+}
+
+/// Runs the command with captured output and kills it with all its children after the timeout
+pub fn run_command(command: &mut Command, timeout: Duration) -> (CommandStatus, String, String) {
+    let start = Instant::now();
+
+    // a process group of its own, so a timeout kills the commands started by sh as well
+    let mut child = match command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0)
+        .spawn() {
+        Ok(child) => { child }
+        Err(error) => { return (CommandStatus::Failed(error.to_string()), String::new(), String::new()); }
+    };
+
+    // the pipes are read while waiting, so a chatty command can not block on a full pipe
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                // commands sent to the background would keep the pipes open
+                kill_process_group(&child);
+                break CommandStatus::Exited(status.code());
+            }
+            Ok(None) if start.elapsed() >= timeout => {
+                kill_process_group(&child);
+                let _ = child.wait();
+                break CommandStatus::TimedOut;
+            }
+            Ok(None) => { thread::sleep(POLL_INTERVAL); }
+            Err(error) => {
+                kill_process_group(&child);
+                let _ = child.wait();
+                break CommandStatus::Failed(error.to_string());
+            }
+        }
+    };
+
+    // a process which left the group may still hold the pipes, it is not waited for after the timeout
+    let deadline = start + timeout.max(start.elapsed() + POLL_INTERVAL);
+    let stdout = stdout.recv_timeout(deadline.saturating_duration_since(Instant::now())).unwrap_or_default();
+    let stderr = stderr.recv_timeout(deadline.saturating_duration_since(Instant::now())).unwrap_or_default();
+    (status, stdout, stderr)
+}
+
+/// reads the pipe until it is closed and sends the output
+fn read_in_background<R: Read + Send + 'static>(pipe: Option<R>) -> mpsc::Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut output);
+        }
+        let _ = sender.send(String::from_utf8_lossy(&output).into_owned());
+    });
+    receiver
+}
+
+fn kill_process_group(child: &Child) {
+    // SAFETY: kill has no memory effects, the negative pid addresses the process group created for the child.
+    // Its id is not reused as long as a process of the group is left
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+    use crate::config::{Probe, ProbeKind};
+    use crate::probe::{ProbeStatus, run_probes};
+
+    fn probe(command: &str, timeout_seconds: Option<u64>) -> Probe {
+        Probe {
+            color: None,
+            kind: ProbeKind::Command { command: command.to_string() },
+            timeout_seconds,
+        }
+    }
+
+    #[test]
+    fn test_run_probes() {
+        let probes = HashMap::from([
+            ("office".to_string(), probe("echo found; echo warning >&2", None)),
+            ("home".to_string(), probe("exit 3", None)),
+            ("hanging".to_string(), probe("sleep 30 | cat", Some(1))),
+            ("slow".to_string(), probe("sleep 1", None)),
+            ("background".to_string(), probe("sleep 30 & exit 0", Some(2))),
+            ("detached".to_string(), probe("setsid sleep 30 & exit 0", Some(2))),
+            ("loopback".to_string(), Probe {
+                color: None,
+                kind: ProbeKind::Interface { interface: "lo".to_string() },
+                timeout_seconds: None,
+            }),
+        ]);
+
+        let outcomes = run_probes(&probes);
+        let names: Vec<&str> = outcomes.iter().map(|outcome| outcome.name.as_str()).collect();
+        assert_eq!(vec!["background", "detached", "hanging", "home", "loopback", "office", "slow"], names);
+
+        // commands left in the background neither block the probe nor change its result
+        let (background, outcomes) = outcomes.split_at(2);
+        for outcome in background {
+            assert!(outcome.detected());
+            assert!(outcome.elapsed < Duration::from_secs(4));
+        }
+
+        assert_eq!(ProbeStatus::TimedOut, outcomes[0].status);
+        assert!(outcomes[0].elapsed < Duration::from_secs(5));
+        assert_eq!(ProbeStatus::NotDetected, outcomes[1].status);
+        assert_eq!("the command exited with 3", outcomes[1].reason);
+        assert!(outcomes[2].detected());
+        assert!(outcomes[3].detected());
+        assert_eq!("found\n", outcomes[3].stdout);
+        assert_eq!("warning\n", outcomes[3].stderr);
+        assert!(outcomes[4].detected());
+    }
+}
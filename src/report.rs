@@ -0,0 +1,194 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{Local, NaiveDate, Utc};
+use cli_table::{Cell, Style};
+use cli_table::format::Justify;
+use serde_derive::Serialize;
+
+use crate::dates::Period;
+use crate::duration_newtype::Duration;
+use crate::models::TimeEntryDetails;
+
+/// What the time entries of a report are aggregated by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportGrouping {
+    Project,
+    Client,
+    Tag,
+    Description,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ReportRow {
+    /// label of the week, month or year if the report is split into periods
+    pub period: Option<String>,
+    pub name: String,
+    pub duration: Duration,
+    /// share of the total duration of the period in percent
+    pub percentage: f64,
+    /// true for the sum of a period
+    #[serde(skip)]
+    pub total: bool,
+}
+
+impl ReportGrouping {
+    pub fn title(&self) -> &'static str {
+        match self {
+            ReportGrouping::Project => { "Project" }
+            ReportGrouping::Client => { "Client" }
+            ReportGrouping::Tag => { "Tag" }
+            ReportGrouping::Description => { "Description" }
+        }
+    }
+
+    /// returns the names a time entry is counted for.
+    /// A time entry with several tags counts for every tag.
+    fn names(&self, details: &TimeEntryDetails) -> Vec<String> {
+        let name = match self {
+            ReportGrouping::Project => { details.project.clone() }
+            ReportGrouping::Client => { details.client.clone() }
+            ReportGrouping::Description => { details.time_entry.description.clone().filter(|description| !description.is_empty()) }
+            ReportGrouping::Tag => {
+                if !details.time_entry.tags.is_empty() {
+                    return details.time_entry.tags.clone();
+                }
+                None
+            }
+        };
+        vec![name.unwrap_or(format!("(no {})", self.title().to_lowercase()))]
+    }
+}
+
+/// Sums up the durations of all time entries per name and period.
+/// Running time entries count until now.
+pub fn aggregate(entries: &[TimeEntryDetails], grouping: ReportGrouping, period: Option<Period>) -> Vec<ReportRow> {
+    let now = Utc::now();
+    let mut periods: BTreeMap<NaiveDate, (chrono::Duration, HashMap<String, chrono::Duration>)> = BTreeMap::new();
+
+    for details in entries {
+        let time_entry = &details.time_entry;
+        let duration = time_entry.stop.unwrap_or(now) - time_entry.start;
+        let date = time_entry.start.with_timezone(&Local).date_naive();
+        let period_start = period
+            .map(|period| period.start_of(date))
+            .unwrap_or(NaiveDate::MIN);
+
+        let (total, durations) = periods
+            .entry(period_start)
+            .or_insert((chrono::Duration::zero(), HashMap::new()));
+        *total = *total + duration;
+        for name in grouping.names(details) {
+            let sum = durations.entry(name).or_insert(chrono::Duration::zero());
+            *sum = *sum + duration;
+        }
+    }
+
+    let mut rows = Vec::new();
+    for (period_start, (total, durations)) in periods {
+        let label = period.map(|period| period.label(period_start));
+        let percentage = |duration: chrono::Duration| if total.num_seconds() > 0 {
+            100.0 * duration.num_seconds() as f64 / total.num_seconds() as f64
+        } else {
+            0.0
+        };
+
+        let mut durations: Vec<(String, chrono::Duration)> = durations.into_iter().collect();
+        durations.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then(a_name.cmp(b_name)));
+
+        for (name, duration) in durations {
+            rows.push(ReportRow {
+                period: label.clone(),
+                name,
+                duration: Duration::of(duration),
+                percentage: percentage(duration),
+                total: false,
+            });
+        }
+        rows.push(ReportRow {
+            period: label,
+            name: "Total".to_string(),
+            duration: Duration::of(total),
+            percentage: percentage(total),
+            total: true,
+        });
+    }
+    rows
+}
+
+impl From<&ReportRow> for Vec<cli_table::CellStruct> {
+    fn from(row: &ReportRow) -> Vec<cli_table::CellStruct> {
+        vec![
+            row.period.clone().unwrap_or_default().cell(),
+            row.name.clone().cell(),
+            row.duration.format_unsigned().cell().justify(Justify::Right),
+            format!("{:.1} %", row.percentage).cell().justify(Justify::Right),
+        ]
+            .into_iter()
+            .map(|cell| cell.bold(row.total))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, Utc};
+    use crate::dates::Period;
+    use crate::models::{TimeEntry, TimeEntryDetails};
+    use crate::report::{aggregate, ReportGrouping};
+
+    fn entry(day: u32, hours: u32, project: Option<&str>, tags: Vec<&str>) -> TimeEntryDetails {
+        let date = NaiveDate::from_ymd_opt(2023, 3, day).unwrap();
+        TimeEntryDetails {
+            time_entry: TimeEntry {
+                id: day as i64,
+                description: None,
+                start: date.and_hms_opt(12, 0, 0).unwrap().and_local_timezone(Utc).unwrap(),
+                stop: Some(date.and_hms_opt(12 + hours, 0, 0).unwrap().and_local_timezone(Utc).unwrap()),
+                project_id: None,
+                workspace_id: None,
+                billable: false,
+                tags: tags.into_iter().map(String::from).collect(),
+            },
+            workspace: None,
+            project: project.map(String::from),
+            client: None,
+        }
+    }
+
+    #[test]
+    fn test_aggregate_by_project() {
+        let entries = vec![
+            entry(6, 3, Some("Rocket"), vec![]),
+            entry(7, 1, None, vec![]),
+            entry(14, 4, Some("Rocket"), vec![]),
+        ];
+
+        let rows = aggregate(&entries, ReportGrouping::Project, None);
+        assert_eq!(3, rows.len());
+        assert_eq!("Rocket", rows[0].name);
+        assert_eq!(7 * 3600, rows[0].duration.chrono_duration.num_seconds());
+        assert_eq!(87.5, rows[0].percentage);
+        assert_eq!("(no project)", rows[1].name);
+        assert!(rows[2].total);
+
+        let rows = aggregate(&entries, ReportGrouping::Project, Some(Period::Week));
+        assert_eq!(Some("2023-W10".to_string()), rows[0].period);
+        assert_eq!(5, rows.len());
+    }
+
+    #[test]
+    fn test_aggregate_by_tag() {
+        let entries = vec![entry(6, 2, None, vec!["rust", "review"])];
+        let rows = aggregate(&entries, ReportGrouping::Tag, None);
+        assert_eq!(3, rows.len());
+        assert_eq!(100.0, rows[0].percentage);
+        assert_eq!(2 * 3600, rows[2].duration.chrono_duration.num_seconds());
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+use crate::config::{Probe, Rule};
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum RuleError {
+    #[error("Rule {0}: expected {1} but found '{2}'")]
+    Unexpected(String, String, String),
+
+    #[error("Rule {0}: the condition ends unexpectedly")]
+    UnexpectedEnd(String),
+
+    #[error("Rule {0}: there is no probe named {1}")]
+    UnknownProbe(String, String),
+
+    #[error("Rule {0} is defined twice")]
+    Duplicate(String),
+}
+
+/// A condition on the detected probes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expression {
+    /// true if the probe with this name was detected
+    Probe(String),
+    Not(Box<Expression>),
+    /// true if all of the expressions are true, written as 'a and b' or all(a, b)
+    All(Vec<Expression>),
+    /// true if any of the expressions is true, written as 'a or b' or any(a, b)
+    Any(Vec<Expression>),
+}
+
+/// A rule whose condition has been parsed
+#[derive(Debug, Clone)]
+pub struct CompiledRule {
+    pub name: String,
+    pub condition: Expression,
+}
+
+impl Expression {
+    pub fn evaluate(&self, detected: &HashSet<&str>) -> bool {
+        match self {
+            Expression::Probe(name) => { detected.contains(name.as_str()) }
+            Expression::Not(expression) => { !expression.evaluate(detected) }
+            Expression::All(expressions) => { expressions.iter().all(|expression| expression.evaluate(detected)) }
+            Expression::Any(expressions) => { expressions.iter().any(|expression| expression.evaluate(detected)) }
+        }
+    }
+
+    /// names of all probes used in the expression
+    pub fn probes(&self) -> Vec<&str> {
+        match self {
+            Expression::Probe(name) => { vec![name.as_str()] }
+            Expression::Not(expression) => { expression.probes() }
+            Expression::All(expressions) | Expression::Any(expressions) => {
+                expressions.iter().flat_map(|expression| expression.probes()).collect()
+            }
+        }
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let join = |expressions: &Vec<Expression>| expressions
+            .iter()
+            .map(|expression| expression.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        match self {
+            Expression::Probe(name) => { write!(f, "{name}") }
+            Expression::Not(expression) => { write!(f, "not {expression}") }
+            Expression::All(expressions) => { write!(f, "all({})", join(expressions)) }
+            Expression::Any(expressions) => { write!(f, "any({})", join(expressions)) }
+        }
+    }
+}
+
+/// Parses and checks all rules, the order of the rules is kept
+pub fn compile(rules: &[Rule], probes: &HashMap<String, Probe>) -> Result<Vec<CompiledRule>, RuleError> {
+    let mut names = HashSet::new();
+    rules
+        .iter()
+        .map(|rule| {
+            if !names.insert(rule.name.as_str()) {
+                return Err(RuleError::Duplicate(rule.name.clone()));
+            }
+            let condition = parse(&rule.name, &rule.when)?;
+            if let Some(unknown) = condition.probes().into_iter().find(|probe| !probes.contains_key(*probe)) {
+                return Err(RuleError::UnknownProbe(rule.name.clone(), unknown.to_string()));
+            }
+            Ok(CompiledRule { name: rule.name.clone(), condition })
+        })
+        .collect()
+}
+
+/// Returns the first rule whose condition is true for the detected probes
+pub fn classify<'a>(rules: &'a [CompiledRule], detected: &HashSet<&str>) -> Option<&'a CompiledRule> {
+    rules.iter().find(|rule| rule.condition.evaluate(detected))
+}
+
+/// Parses a condition like 'office_wifi and not (vpn or any(hotspot, tethering))'
+pub fn parse(rule: &str, condition: &str) -> Result<Expression, RuleError> {
+    let mut parser = Parser {
+        rule,
+        tokens: tokenize(condition).into_iter().peekable(),
+    };
+    let expression = parser.parse_or()?;
+    match parser.tokens.next() {
+        None => { Ok(expression) }
+        Some(token) => { Err(parser.unexpected("the end", &token)) }
+    }
+}
+
+fn tokenize(condition: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    for character in condition.chars() {
+        if character.is_alphanumeric() || character == '_' || character == '-' || character == '.' {
+            word.push(character);
+            continue;
+        }
+        if !word.is_empty() {
+            tokens.push(std::mem::take(&mut word));
+        }
+        if !character.is_whitespace() {
+            tokens.push(character.to_string());
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+    tokens
+}
+
+struct Parser<'a> {
+    rule: &'a str,
+    tokens: Peekable<IntoIter<String>>,
+}
+
+impl Parser<'_> {
+    fn unexpected(&self, expected: &str, found: &str) -> RuleError {
+        RuleError::Unexpected(self.rule.to_string(), expected.to_string(), found.to_string())
+    }
+
+    fn next(&mut self) -> Result<String, RuleError> {
+        self.tokens.next().ok_or(RuleError::UnexpectedEnd(self.rule.to_string()))
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), RuleError> {
+        let token = self.next()?;
+        if token != expected {
+            return Err(self.unexpected(&format!("'{expected}'"), &token));
+        }
+        Ok(())
+    }
+
+    fn next_is(&mut self, token: &str) -> bool {
+        self.tokens.next_if(|next| next == token).is_some()
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, RuleError> {
+        let mut expressions = vec![self.parse_and()?];
+        while self.next_is("or") {
+            expressions.push(self.parse_and()?);
+        }
+        Ok(if expressions.len() == 1 { expressions.remove(0) } else { Expression::Any(expressions) })
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, RuleError> {
+        let mut expressions = vec![self.parse_not()?];
+        while self.next_is("and") {
+            expressions.push(self.parse_not()?);
+        }
+        Ok(if expressions.len() == 1 { expressions.remove(0) } else { Expression::All(expressions) })
+    }
+
+    fn parse_not(&mut self) -> Result<Expression, RuleError> {
+        if self.next_is("not") {
+            return Ok(Expression::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, RuleError> {
+        let token = self.next()?;
+        match token.as_str() {
+            "(" => {
+                let expression = self.parse_or()?;
+                self.expect(")")?;
+                Ok(expression)
+            }
+            "all" | "any" if self.next_is("(") => {
+                let mut expressions = vec![self.parse_or()?];
+                while self.next_is(",") {
+                    expressions.push(self.parse_or()?);
+                }
+                self.expect(")")?;
+                Ok(if token == "all" { Expression::All(expressions) } else { Expression::Any(expressions) })
+            }
+            "and" | "or" | ")" | "," => { Err(self.unexpected("a probe", &token)) }
+            _ if token.chars().all(|character| character.is_alphanumeric() || "_-.".contains(character)) => {
+                Ok(Expression::Probe(token))
+            }
+            _ => { Err(self.unexpected("a probe", &token)) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use crate::config::{Probe, ProbeKind, Rule};
+    use crate::rules::{classify, compile, Expression, parse, RuleError};
+
+    #[test]
+    fn test_parse() {
+        let expression = parse("office", "office_wifi and not (vpn or any(hotspot, tethering))").unwrap();
+        assert_eq!("all(office_wifi, not any(vpn, any(hotspot, tethering)))", expression.to_string());
+        assert_eq!(Expression::Any(vec![
+            Expression::All(vec![Expression::Probe("a".to_string()), Expression::Probe("b".to_string())]),
+            Expression::Probe("c".to_string()),
+        ]), parse("x", "a and b or c").unwrap());
+
+        assert_eq!(Err(RuleError::UnexpectedEnd("x".to_string())), parse("x", "a and"));
+        assert_eq!(Err(RuleError::Unexpected("x".to_string(), "')'".to_string(), "b".to_string())), parse("x", "all(a b)"));
+        assert!(parse("x", "a )").is_err());
+        assert!(parse("x", "").is_err());
+    }
+
+    #[test]
+    fn test_classify() {
+        let probe = |command: &str| Probe {
+            color: None,
+            kind: ProbeKind::Command { command: command.to_string() },
+            timeout_seconds: None,
+        };
+        let probes: HashMap<String, Probe> = ["fritzbox", "vpn", "office_wifi"]
+            .into_iter()
+            .map(|name| (name.to_string(), probe("true")))
+            .collect();
+        let rule = |name: &str, when: &str| Rule { name: name.to_string(), when: when.to_string() };
+        let rules = compile(&[
+            rule("office", "office_wifi or (vpn and not fritzbox)"),
+            rule("home", "fritzbox"),
+        ], &probes).unwrap();
+
+        // vpn at home is still home, exactly one rule wins
+        let classify_names = |detected: &[&str]| classify(&rules, &detected.iter().copied().collect::<HashSet<&str>>())
+            .map(|rule| rule.name.clone());
+        assert_eq!(Some("home".to_string()), classify_names(&["fritzbox", "vpn"]));
+        assert_eq!(Some("office".to_string()), classify_names(&["vpn"]));
+        assert_eq!(Some("office".to_string()), classify_names(&["office_wifi", "fritzbox"]));
+        assert_eq!(None, classify_names(&[]));
+
+        assert_eq!(
+            RuleError::UnknownProbe("office".to_string(), "tun0".to_string()),
+            compile(&[rule("office", "fritzbox and not tun0")], &probes).unwrap_err()
+        );
+        assert_eq!(
+            RuleError::Duplicate("home".to_string()),
+            compile(&[rule("home", "fritzbox"), rule("home", "vpn")], &probes).unwrap_err()
+        );
+    }
+}
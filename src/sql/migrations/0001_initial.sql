@@ -1,5 +1,3 @@
-BEGIN;
-
 CREATE TABLE IF NOT EXISTS "office_location" (
     instant TEXT  NOT NULL,
     location TEXT NOT NULL,
@@ -82,5 +80,3 @@ SELECT
     ) AS events
 FROM timesheet_delta AS timesheet
 ORDER BY timesheet.date;
-
-COMMIT;
\ No newline at end of file
@@ -0,0 +1,80 @@
+-- 'schedule' rows are derived from the configured work week and get refreshed,
+-- every other source is kept as it is
+ALTER TABLE expected_duration ADD COLUMN source TEXT NOT NULL DEFAULT 'schedule';
+
+DROP VIEW IF EXISTS timesheet;
+CREATE VIEW timesheet AS WITH
+    -- every day that has either worked time or an expectation
+    days AS (
+        SELECT DATE(start) AS date FROM time_entries
+        UNION
+        SELECT DATE(date) AS date FROM expected_duration
+    ),
+    -- group all time entries per their dates and sum the durations
+    worked_time_per_day AS (
+        SELECT
+            DATE(start) as date,
+            SUM(
+                CAST(
+                    (julianday(datetime(IFNULL(stop, datetime('now')))) - julianday(datetime(start))) * 86400
+                AS INTEGER)
+            ) AS actual_duration
+        FROM time_entries
+        GROUP BY DATE(start)
+    ),
+    -- calculate the delta of actual time worked to expected work time
+    timesheet_delta AS (
+        SELECT
+            days.date as date,
+            IFNULL(worked_time_per_day.actual_duration, 0) AS actual_duration,
+            IFNULL(expected_duration.duration, 0) AS expected_duration,
+            (IFNULL(worked_time_per_day.actual_duration, 0) - IFNULL(expected_duration.duration, 0)) AS delta,
+
+            -- beginning of the 'typical' workday
+            CASE
+                WHEN worked_time_per_day.actual_duration > 0 THEN "08:00:00"
+                ELSE "00:00:00"
+            END as normalized_start_of_business,
+            -- if started at the 'typical' time, done the obligatory breaks, which time would we currently?
+            -- 28800 is 08:00
+            CASE
+                WHEN worked_time_per_day.actual_duration > 0 THEN
+                    time(28800 + worked_time_per_day.actual_duration  +
+                        CASE -- after 6 hours there needs to be a break of 45 minutes
+                            WHEN worked_time_per_day.actual_duration > 21600  THEN 2700
+                            ELSE 0
+                        END,
+                    'unixepoch')
+                ELSE "00:00:00"
+            END as normalized_end_of_business
+        FROM days
+        LEFT JOIN worked_time_per_day
+        ON days.date = worked_time_per_day.date
+        LEFT JOIN expected_duration
+        ON days.date = DATE(expected_duration.date)
+    ),
+    events_per_day AS (
+	SELECT
+       	    DATE(instant) as date,
+	    location as event_name
+	FROM office_location
+	GROUP BY DATE(instant), location
+    )
+SELECT
+    date,
+    actual_duration,
+    expected_duration,
+    delta,
+    ( -- sum all deltas before this date
+        SELECT SUM(delta) FROM timesheet_delta AS saldo_table WHERE timesheet.date >= saldo_table.date
+    ) AS saldo,
+    normalized_start_of_business,
+    normalized_end_of_business,
+    (
+        SELECT GROUP_CONCAT(event_name, ", ")
+        FROM events_per_day
+        GROUP BY events_per_day.date
+        HAVING events_per_day.date = timesheet.date
+    ) AS events
+FROM timesheet_delta AS timesheet
+ORDER BY timesheet.date;
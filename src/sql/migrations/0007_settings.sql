@@ -0,0 +1,5 @@
+-- state papierkram needs to remember between runs, like the last toggl sync
+CREATE TABLE IF NOT EXISTS settings (
+  key TEXT PRIMARY KEY NOT NULL,
+  value TEXT NOT NULL
+);
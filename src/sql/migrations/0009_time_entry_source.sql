@@ -0,0 +1,6 @@
+-- where an imported time entry comes from, NULL for entries from toggl and local tracking
+ALTER TABLE time_entries ADD COLUMN source TEXT;
+-- id of the time entry in the source, used to import the same file again without duplicates
+ALTER TABLE time_entries ADD COLUMN external_id TEXT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS time_entries_source_external_id ON time_entries (source, external_id);
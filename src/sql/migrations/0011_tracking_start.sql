@@ -0,0 +1,125 @@
+-- the timesheet begins at the tracking start stored in the settings as 'saldo.tracking_start'.
+-- The saldo carried over from before is stored as saldo adjustment with the source 'initial'
+
+DROP VIEW IF EXISTS timesheet;
+CREATE VIEW timesheet AS WITH
+    -- the first day that counts, everything before is history from before papierkram was used
+    tracking_start AS (
+        SELECT IFNULL((SELECT value FROM settings WHERE key = 'saldo.tracking_start'), '0000-01-01') AS date
+    ),
+    -- every day since the tracking start that has either worked time or an expectation
+    days AS (
+        SELECT date FROM (
+            SELECT DATE(start) AS date FROM time_entries
+            UNION
+            SELECT DATE(date) AS date FROM expected_duration
+            UNION
+            SELECT DATE(date) AS date FROM saldo_adjustments
+        )
+        WHERE date >= (SELECT date FROM tracking_start)
+    ),
+    -- absent time counts as worked time, a half day of absence only covers half the expectation.
+    -- compensation days are taken from the overtime, so they do not count
+    absent_time_per_day AS (
+        SELECT
+            DATE(absences.date) AS date,
+            CAST(absences.fraction * expected_duration.duration AS INTEGER) AS absent_duration
+        FROM absences
+        JOIN expected_duration
+        ON DATE(absences.date) = DATE(expected_duration.date)
+        WHERE absences.kind != 'compensation'
+    ),
+    -- group all time entries per their dates and sum the durations
+    worked_time_per_day AS (
+        SELECT
+            DATE(start) as date,
+            SUM(
+                CAST(
+                    ROUND((julianday(datetime(IFNULL(stop, datetime('now')))) - julianday(datetime(start))) * 86400)
+                AS INTEGER)
+            ) AS actual_duration,
+            MIN(start) AS first_start,
+            MAX(IFNULL(stop, datetime('now'))) AS last_stop
+        FROM time_entries
+        GROUP BY DATE(start)
+    ),
+    -- calculate the delta of actual time worked to expected work time
+    timesheet_delta AS (
+        SELECT
+            days.date as date,
+            IFNULL(worked_time_per_day.actual_duration, 0) AS actual_duration,
+            IFNULL(expected_duration.duration, 0) AS expected_duration,
+            (
+                IFNULL(worked_time_per_day.actual_duration, 0)
+                + IFNULL(absent_time_per_day.absent_duration, 0)
+                - IFNULL(expected_duration.duration, 0)
+            ) AS delta,
+
+            -- the real begin and end of the workday in local time
+            IFNULL(time(worked_time_per_day.first_start, 'localtime'), "00:00:00") AS start_of_business,
+            IFNULL(time(worked_time_per_day.last_stop, 'localtime'), "00:00:00") AS end_of_business,
+            -- every gap between the first start and the last stop is a break
+            IFNULL(MAX(0,
+                CAST(
+                    ROUND((julianday(datetime(worked_time_per_day.last_stop)) - julianday(datetime(worked_time_per_day.first_start))) * 86400)
+                AS INTEGER) - worked_time_per_day.actual_duration
+            ), 0) AS break_duration
+        FROM days
+        LEFT JOIN worked_time_per_day
+        ON days.date = worked_time_per_day.date
+        LEFT JOIN expected_duration
+        ON days.date = DATE(expected_duration.date)
+        LEFT JOIN absent_time_per_day
+        ON days.date = absent_time_per_day.date
+    ),
+    projects_per_day AS (
+        SELECT DISTINCT
+            DATE(time_entries.start) AS date,
+            projects.name AS project_name
+        FROM time_entries
+        JOIN projects
+        ON time_entries.project_id = projects.id
+    ),
+    events_per_day AS (
+	SELECT
+       	    DATE(instant) as date,
+	    location as event_name
+	FROM office_location
+	GROUP BY DATE(instant), location
+    )
+SELECT
+    date,
+    actual_duration,
+    expected_duration,
+    delta,
+    ( -- sum all deltas and adjustments before this date
+        SELECT SUM(delta) FROM timesheet_delta AS saldo_table WHERE timesheet.date >= saldo_table.date
+    ) + IFNULL((
+        SELECT SUM(amount) FROM saldo_adjustments
+        WHERE timesheet.date >= DATE(saldo_adjustments.date)
+        AND DATE(saldo_adjustments.date) >= (SELECT date FROM tracking_start)
+    ), 0) AS saldo,
+    start_of_business,
+    end_of_business,
+    break_duration,
+    (
+        SELECT GROUP_CONCAT(label, ", ") FROM (
+            -- holidays and absences are listed before the detected events
+            SELECT name AS label FROM holidays WHERE holidays.date = timesheet.date
+            UNION ALL
+            SELECT
+                kind || CASE WHEN fraction < 1 THEN ' (half day)' ELSE '' END AS label
+            FROM absences WHERE DATE(absences.date) = timesheet.date
+            UNION ALL
+            SELECT 'saldo ' || reason AS label FROM saldo_adjustments WHERE DATE(saldo_adjustments.date) = timesheet.date
+            UNION ALL
+            SELECT event_name AS label FROM events_per_day WHERE events_per_day.date = timesheet.date
+        )
+    ) AS events,
+    (
+        SELECT GROUP_CONCAT(project_name, ", ")
+        FROM projects_per_day
+        WHERE projects_per_day.date = timesheet.date
+    ) AS projects
+FROM timesheet_delta AS timesheet
+ORDER BY timesheet.date;
@@ -0,0 +1,71 @@
+-- raw detections older than the retention are compacted into location_intervals, see: papierkram db vacuum.
+-- The presence intervals are built from both, so compacted stays still count in the timesheet
+
+CREATE TABLE IF NOT EXISTS location_intervals (
+    location TEXT NOT NULL,
+    -- first and last detection of the stay
+    start TEXT NOT NULL,
+    stop TEXT NOT NULL,
+    -- number of raw detections the interval replaces
+    detections INTEGER NOT NULL,
+    PRIMARY KEY (location, start)
+);
+
+DROP VIEW IF EXISTS presence_intervals;
+CREATE VIEW presence_intervals AS WITH
+    max_gap AS (
+        SELECT IFNULL((SELECT CAST(value AS INTEGER) FROM settings WHERE key = 'timeline.max_gap_seconds'), 900) AS seconds
+    ),
+    -- raw detections are intervals without duration
+    detections AS (
+        SELECT
+            location,
+            instant AS start,
+            instant AS stop,
+            1 AS detections
+        FROM office_location
+        UNION ALL
+        SELECT
+            location,
+            start,
+            stop,
+            detections
+        FROM location_intervals
+    ),
+    detection_seconds AS (
+        SELECT
+            location,
+            start,
+            stop,
+            detections,
+            CAST(ROUND(julianday(start) * 86400) AS INTEGER) AS start_seconds,
+            CAST(ROUND(julianday(stop) * 86400) AS INTEGER) AS stop_seconds
+        FROM detections
+    ),
+    -- a detection starts a new interval if the previous detection of the same location is too long ago
+    interval_starts AS (
+        SELECT
+            *,
+            CASE
+                WHEN start_seconds - LAG(stop_seconds) OVER (PARTITION BY location ORDER BY start_seconds) <= (SELECT seconds FROM max_gap)
+                THEN 0 ELSE 1
+            END AS is_start
+        FROM detection_seconds
+    ),
+    -- number the intervals of every location by counting the starts so far
+    numbered_detections AS (
+        SELECT
+            *,
+            SUM(is_start) OVER (PARTITION BY location ORDER BY start_seconds ROWS UNBOUNDED PRECEDING) AS interval_number
+        FROM interval_starts
+    )
+SELECT
+    DATE(MIN(start), 'localtime') AS date,
+    location,
+    MIN(start) AS start,
+    MAX(stop) AS stop,
+    MAX(stop_seconds) - MIN(start_seconds) AS duration,
+    SUM(detections) AS detections
+FROM numbered_detections
+GROUP BY location, interval_number
+ORDER BY start;
@@ -0,0 +1,70 @@
+-- a stay ends as soon as a different location is detected without it, so stays at different places do not overlap.
+-- The presence intervals belong to the same day as in the timesheet, which is the UTC date of their start
+
+DROP VIEW IF EXISTS presence_intervals;
+CREATE VIEW presence_intervals AS WITH
+    max_gap AS (
+        SELECT IFNULL((SELECT CAST(value AS INTEGER) FROM settings WHERE key = 'timeline.max_gap_seconds'), 900) AS seconds
+    ),
+    -- raw detections are intervals without duration
+    detections AS (
+        SELECT
+            location,
+            instant AS start,
+            instant AS stop,
+            1 AS detections
+        FROM office_location
+        UNION ALL
+        SELECT
+            location,
+            start,
+            stop,
+            detections
+        FROM location_intervals
+    ),
+    detection_seconds AS (
+        SELECT
+            location,
+            start,
+            stop,
+            detections,
+            CAST(ROUND(julianday(start) * 86400) AS INTEGER) AS start_seconds,
+            CAST(ROUND(julianday(stop) * 86400) AS INTEGER) AS stop_seconds
+        FROM detections
+    ),
+    -- all locations detected in the same second belong to the same run of detect
+    detection_runs AS (
+        SELECT
+            *,
+            DENSE_RANK() OVER (ORDER BY start_seconds) AS run
+        FROM detection_seconds
+    ),
+    -- a detection starts a new interval if the previous detection of the same location is too long ago
+    -- or if a run without this location was in between
+    interval_starts AS (
+        SELECT
+            *,
+            CASE
+                WHEN start_seconds - LAG(stop_seconds) OVER (PARTITION BY location ORDER BY start_seconds) <= (SELECT seconds FROM max_gap)
+                AND run - LAG(run) OVER (PARTITION BY location ORDER BY start_seconds) <= 1
+                THEN 0 ELSE 1
+            END AS is_start
+        FROM detection_runs
+    ),
+    -- number the intervals of every location by counting the starts so far
+    numbered_detections AS (
+        SELECT
+            *,
+            SUM(is_start) OVER (PARTITION BY location ORDER BY start_seconds ROWS UNBOUNDED PRECEDING) AS interval_number
+        FROM interval_starts
+    )
+SELECT
+    DATE(MIN(start)) AS date,
+    location,
+    MIN(start) AS start,
+    MAX(stop) AS stop,
+    MAX(stop_seconds) - MIN(start_seconds) AS duration,
+    SUM(detections) AS detections
+FROM numbered_detections
+GROUP BY location, interval_number
+ORDER BY start;
@@ -0,0 +1,150 @@
+-- absences are keyed on their date and kind, so a half day of vacation and a half day of sick leave
+-- on the same date no longer overwrite each other. The timesheet counts at most a full day of absence
+
+DROP VIEW IF EXISTS timesheet;
+
+CREATE TABLE absences_by_kind (
+  date DATETIME NOT NULL,
+  kind TEXT NOT NULL,
+  -- 1.0 for a full day, 0.5 for a half day
+  fraction REAL NOT NULL,
+  PRIMARY KEY (date, kind)
+);
+INSERT INTO absences_by_kind (date, kind, fraction) SELECT date, kind, fraction FROM absences;
+DROP TABLE absences;
+ALTER TABLE absences_by_kind RENAME TO absences;
+
+CREATE VIEW timesheet AS WITH
+    -- the first day that counts, everything before is history from before papierkram was used
+    tracking_start AS (
+        SELECT IFNULL((SELECT value FROM settings WHERE key = 'saldo.tracking_start'), '0000-01-01') AS date
+    ),
+    -- every day since the tracking start that has either worked time or an expectation
+    days AS (
+        SELECT date FROM (
+            SELECT DATE(start) AS date FROM time_entries
+            UNION
+            SELECT DATE(date) AS date FROM expected_duration
+            UNION
+            SELECT DATE(date) AS date FROM saldo_adjustments
+        )
+        WHERE date >= (SELECT date FROM tracking_start)
+    ),
+    -- absent time counts as worked time, a half day of absence only covers half the expectation.
+    -- Absences of different kinds on the same day add up, but never to more than the whole expectation.
+    -- compensation days are taken from the overtime, so they do not count
+    absent_time_per_day AS (
+        SELECT
+            DATE(absences.date) AS date,
+            CAST(MIN(1.0, SUM(absences.fraction)) * expected_duration.duration AS INTEGER) AS absent_duration
+        FROM absences
+        JOIN expected_duration
+        ON DATE(absences.date) = DATE(expected_duration.date)
+        WHERE absences.kind != 'compensation'
+        GROUP BY DATE(absences.date)
+    ),
+    -- group all time entries per their dates and sum the durations
+    worked_time_per_day AS (
+        SELECT
+            DATE(start) as date,
+            SUM(
+                CAST(
+                    ROUND((julianday(datetime(IFNULL(stop, datetime('now')))) - julianday(datetime(start))) * 86400)
+                AS INTEGER)
+            ) AS actual_duration,
+            MIN(start) AS first_start,
+            MAX(IFNULL(stop, datetime('now'))) AS last_stop
+        FROM time_entries
+        GROUP BY DATE(start)
+    ),
+    -- calculate the delta of actual time worked to expected work time
+    timesheet_delta AS (
+        SELECT
+            days.date as date,
+            IFNULL(worked_time_per_day.actual_duration, 0) AS actual_duration,
+            IFNULL(expected_duration.duration, 0) AS expected_duration,
+            (
+                IFNULL(worked_time_per_day.actual_duration, 0)
+                + IFNULL(absent_time_per_day.absent_duration, 0)
+                - IFNULL(expected_duration.duration, 0)
+            ) AS delta,
+
+            -- the real begin and end of the workday in local time
+            IFNULL(time(worked_time_per_day.first_start, 'localtime'), "00:00:00") AS start_of_business,
+            IFNULL(time(worked_time_per_day.last_stop, 'localtime'), "00:00:00") AS end_of_business,
+            -- every gap between the first start and the last stop is a break
+            IFNULL(MAX(0,
+                CAST(
+                    ROUND((julianday(datetime(worked_time_per_day.last_stop)) - julianday(datetime(worked_time_per_day.first_start))) * 86400)
+                AS INTEGER) - worked_time_per_day.actual_duration
+            ), 0) AS break_duration
+        FROM days
+        LEFT JOIN worked_time_per_day
+        ON days.date = worked_time_per_day.date
+        LEFT JOIN expected_duration
+        ON days.date = DATE(expected_duration.date)
+        LEFT JOIN absent_time_per_day
+        ON days.date = absent_time_per_day.date
+    ),
+    projects_per_day AS (
+        SELECT DISTINCT
+            DATE(time_entries.start) AS date,
+            projects.name AS project_name
+        FROM time_entries
+        JOIN projects
+        ON time_entries.project_id = projects.id
+    ),
+    -- the time spent at every location per day, taken from the presence intervals
+    presence_per_day AS (
+        SELECT
+            date,
+            location,
+            SUM(duration) AS duration,
+            MIN(start) AS first_start
+        FROM presence_intervals
+        GROUP BY date, location
+    )
+SELECT
+    date,
+    actual_duration,
+    expected_duration,
+    delta,
+    ( -- sum all deltas and adjustments before this date
+        SELECT SUM(delta) FROM timesheet_delta AS saldo_table WHERE timesheet.date >= saldo_table.date
+    ) + IFNULL((
+        SELECT SUM(amount) FROM saldo_adjustments
+        WHERE timesheet.date >= DATE(saldo_adjustments.date)
+        AND DATE(saldo_adjustments.date) >= (SELECT date FROM tracking_start)
+    ), 0) AS saldo,
+    start_of_business,
+    end_of_business,
+    break_duration,
+    (
+        SELECT GROUP_CONCAT(label, ", ") FROM (
+            -- holidays and absences are listed before the time spent at the detected locations
+            SELECT name AS label FROM holidays WHERE holidays.date = timesheet.date
+            UNION ALL
+            SELECT label FROM (
+                SELECT
+                    kind || CASE WHEN fraction < 1 THEN ' (half day)' ELSE '' END AS label
+                FROM absences WHERE DATE(absences.date) = timesheet.date
+                ORDER BY kind
+            )
+            UNION ALL
+            SELECT 'saldo ' || reason AS label FROM saldo_adjustments WHERE DATE(saldo_adjustments.date) = timesheet.date
+            UNION ALL
+            SELECT label FROM (
+                SELECT
+                    location || ' ' || printf('%02d:%02d', duration / 3600, duration % 3600 / 60) AS label
+                FROM presence_per_day WHERE presence_per_day.date = timesheet.date
+                ORDER BY first_start
+            )
+        )
+    ) AS events,
+    (
+        SELECT GROUP_CONCAT(project_name, ", ")
+        FROM projects_per_day
+        WHERE projects_per_day.date = timesheet.date
+    ) AS projects
+FROM timesheet_delta AS timesheet
+ORDER BY timesheet.date;
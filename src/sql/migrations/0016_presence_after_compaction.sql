@@ -0,0 +1,84 @@
+-- compacted location intervals are complete stays, a later detection of the same location starts a new one.
+-- Runs are numbered by the start and stop of every detection and interval, so a run without a location in between
+-- is still found when the raw detections around it are compacted already
+
+DROP VIEW IF EXISTS presence_intervals;
+CREATE VIEW presence_intervals AS WITH
+    max_gap AS (
+        SELECT IFNULL((SELECT CAST(value AS INTEGER) FROM settings WHERE key = 'timeline.max_gap_seconds'), 900) AS seconds
+    ),
+    -- raw detections are intervals without duration
+    detections AS (
+        SELECT
+            location,
+            instant AS start,
+            instant AS stop,
+            1 AS detections,
+            0 AS compacted
+        FROM office_location
+        UNION ALL
+        SELECT
+            location,
+            start,
+            stop,
+            detections,
+            1 AS compacted
+        FROM location_intervals
+    ),
+    detection_seconds AS (
+        SELECT
+            *,
+            CAST(ROUND(julianday(start) * 86400) AS INTEGER) AS start_seconds,
+            CAST(ROUND(julianday(stop) * 86400) AS INTEGER) AS stop_seconds
+        FROM detections
+    ),
+    -- all locations detected in the same second belong to the same run of detect
+    runs AS (
+        SELECT
+            seconds,
+            ROW_NUMBER() OVER (ORDER BY seconds) AS run
+        FROM (
+            SELECT start_seconds AS seconds FROM detection_seconds
+            UNION
+            SELECT stop_seconds AS seconds FROM detection_seconds
+        )
+    ),
+    detection_runs AS (
+        SELECT
+            detection_seconds.*,
+            start_runs.run AS start_run,
+            stop_runs.run AS stop_run
+        FROM detection_seconds
+        JOIN runs AS start_runs ON start_runs.seconds = detection_seconds.start_seconds
+        JOIN runs AS stop_runs ON stop_runs.seconds = detection_seconds.stop_seconds
+    ),
+    -- a detection starts a new interval if the previous detection of the same location is compacted or too long ago
+    -- or if a run without this location was in between
+    interval_starts AS (
+        SELECT
+            *,
+            CASE
+                WHEN LAG(compacted) OVER (PARTITION BY location ORDER BY start_seconds) = 0
+                AND start_seconds - LAG(stop_seconds) OVER (PARTITION BY location ORDER BY start_seconds) <= (SELECT seconds FROM max_gap)
+                AND start_run - LAG(stop_run) OVER (PARTITION BY location ORDER BY start_seconds) <= 1
+                THEN 0 ELSE 1
+            END AS is_start
+        FROM detection_runs
+    ),
+    -- number the intervals of every location by counting the starts so far
+    numbered_detections AS (
+        SELECT
+            *,
+            SUM(is_start) OVER (PARTITION BY location ORDER BY start_seconds ROWS UNBOUNDED PRECEDING) AS interval_number
+        FROM interval_starts
+    )
+SELECT
+    DATE(MIN(start)) AS date,
+    location,
+    MIN(start) AS start,
+    MAX(stop) AS stop,
+    MAX(stop_seconds) - MIN(start_seconds) AS duration,
+    SUM(detections) AS detections
+FROM numbered_detections
+GROUP BY location, interval_number
+ORDER BY start;
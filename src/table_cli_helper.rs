@@ -55,6 +55,7 @@ pub fn cell_style_duration_signed(_cell: cli_table::CellStruct, value: &Duration
     }
 }
 
+#[allow(dead_code)]
 pub fn empty_if_duration_null(cell: cli_table::CellStruct, value: &Duration) -> cli_table::CellStruct {
     if value.chrono_duration.num_seconds() > 0 {
         cell
@@ -71,6 +72,10 @@ pub fn empty_if_time_null(cell: cli_table::CellStruct, value: &NaiveTime) -> cli
     }
 }
 
+pub fn cell_style_warning(cell: cli_table::CellStruct, _value: &str) -> cli_table::CellStruct {
+    cell.foreground_color(Some(Color::Red))
+}
+
 pub trait TableFormatter {
     fn format_table(self) -> TableStruct;
 }
@@ -1,12 +1,14 @@
 use chrono::{DateTime, NaiveDate, Utc};
 
+use serde::de::DeserializeOwned;
 use serde_json::Value;
 use crate::config::Toggl;
-use crate::models::TimeEntry;
+use crate::models::{Client, Project, Tag, TimeEntry, Workspace};
 use crate::toggl::TogglApiError::ValueError;
 
-static TOGGL_API_HOST: &str = "api.track.toggl.com";
+static TOGGL_API_URL: &str = "https://api.track.toggl.com";
 static TOGGL_TIME_ENTRIES_ENDPOINT: &str = "/api/v9/me/time_entries";
+static TOGGL_WORKSPACES_ENDPOINT: &str = "/api/v9/me/workspaces";
 
 #[allow(dead_code)]
 #[derive(thiserror::Error, Debug)]
@@ -29,13 +31,92 @@ pub enum TogglApiError {
 }
 
 
+/// Time entries which changed on the toggl server
+#[derive(Debug, Default)]
+pub struct TimeEntryChanges {
+    pub updated: Vec<TimeEntry>,
+    /// ids of the time entries deleted on the server
+    pub deleted: Vec<i64>,
+    /// ids of the changed time entries which could not be read
+    pub unreadable: Vec<i64>,
+}
+
+/// All time entries between two dates on the toggl server
+#[derive(Debug, Default)]
+pub struct TimeEntryWindow {
+    pub time_entries: Vec<TimeEntry>,
+    /// ids of every time entry toggl returned, including the unreadable ones
+    pub ids: Vec<i64>,
+    /// ids of the time entries which could not be read
+    pub unreadable: Vec<i64>,
+}
+
 pub fn get_time_entries(
     config: &Toggl,
     start_date: &NaiveDate,
     end_date: &NaiveDate)
-    -> Result<Vec<TimeEntry>, TogglApiError>
+    -> Result<TimeEntryWindow, TogglApiError>
+{
+    let mut window = TimeEntryWindow::default();
+
+    for entry in request_time_entries(config, &format!("start_date={start_date}&end_date={end_date}"))? {
+        window.ids.push(serde_json::from_value(entry["id"].clone())?);
+        match read_time_entry(&entry) {
+            Ok(time_entry) => { window.time_entries.push(time_entry); }
+            Err(id) => { window.unreadable.push(id); }
+        }
+    }
+
+    Ok(window)
+}
+
+/// returns all time entries which were created, changed or deleted since the given instant.
+/// Toggl only keeps track of changes of the last three months.
+pub fn get_time_entries_since(
+    config: &Toggl,
+    since: &DateTime<Utc>)
+    -> Result<TimeEntryChanges, TogglApiError>
 {
-    let url = format!("https://{TOGGL_API_HOST}{TOGGL_TIME_ENTRIES_ENDPOINT}?start_date={start_date}&end_date={end_date}");
+    let mut changes = TimeEntryChanges::default();
+
+    for entry in request_time_entries(config, &format!("since={}", since.timestamp()))? {
+        if entry["server_deleted_at"].is_null() {
+            match read_time_entry(&entry) {
+                Ok(time_entry) => { changes.updated.push(time_entry); }
+                Err(id) => { changes.unreadable.push(id); }
+            }
+        } else {
+            changes.deleted.push(serde_json::from_value(entry["id"].clone())?);
+        }
+    }
+
+    Ok(changes)
+}
+
+pub fn get_workspaces(config: &Toggl) -> Result<Vec<Workspace>, TogglApiError> {
+    request_list(config, TOGGL_WORKSPACES_ENDPOINT)
+}
+
+pub fn get_projects(config: &Toggl, workspace_id: i64) -> Result<Vec<Project>, TogglApiError> {
+    request_list(config, &format!("/api/v9/workspaces/{workspace_id}/projects"))
+}
+
+pub fn get_clients(config: &Toggl, workspace_id: i64) -> Result<Vec<Client>, TogglApiError> {
+    request_list(config, &format!("/api/v9/workspaces/{workspace_id}/clients"))
+}
+
+pub fn get_tags(config: &Toggl, workspace_id: i64) -> Result<Vec<Tag>, TogglApiError> {
+    request_list(config, &format!("/api/v9/workspaces/{workspace_id}/tags"))
+}
+
+fn request_time_entries(config: &Toggl, query: &str) -> Result<Vec<Value>, TogglApiError> {
+    request_list(config, &format!("{TOGGL_TIME_ENTRIES_ENDPOINT}?{query}"))
+}
+
+/// requests a json list from the toggl api. Toggl answers empty lists with null.
+fn request_list<T: DeserializeOwned>(config: &Toggl, path: &str) -> Result<Vec<T>, TogglApiError> {
+    let api_url = config.api_url.as_deref().unwrap_or(TOGGL_API_URL).trim_end_matches('/');
+    let url = format!("{api_url}{path}");
     log::debug!("Requesting curl -u {}:{} {url}", config.username, config.password);
 
     let client = reqwest::blocking::Client::new();
@@ -44,17 +125,19 @@ pub fn get_time_entries(
         .basic_auth(
             &config.username,
             Some(&config.password))
-        .send()?.text()?;
-
-
-    let time_entries: Vec<TimeEntry> = serde_json::from_str::<Vec<Value>>(&response)?
-        .iter()
-        .map(value_as_time_entry)
-        .filter_map(|x| x.ok())
-        .collect();
+        .send()?
+        .error_for_status()?
+        .text()?;
 
+    Ok(serde_json::from_str::<Option<Vec<T>>>(&response)?.unwrap_or_default())
+}
 
-    Ok(time_entries)
+/// converts a time entry from toggl or logs why it could not be read and returns its id
+fn read_time_entry(entry: &Value) -> Result<TimeEntry, i64> {
+    value_as_time_entry(entry).map_err(|error| {
+        log::warn!("Could not read time entry from toggl: {error:?}; Entry: {entry}");
+        entry["id"].as_i64().unwrap_or_default()
+    })
 }
 
 fn value_as_time_entry(entry: &Value) -> Result<TimeEntry, TogglApiError> {
@@ -67,6 +150,8 @@ fn value_as_time_entry(entry: &Value) -> Result<TimeEntry, TogglApiError> {
         stop: as_naive_date_time(entry["stop"].clone())?,
         project_id: serde_json::from_value(entry["project_id"].clone())?,
         workspace_id: serde_json::from_value(entry["workspace_id"].clone())?,
+        billable: serde_json::from_value::<Option<bool>>(entry["billable"].clone())?.unwrap_or(false),
+        tags: serde_json::from_value::<Option<Vec<String>>>(entry["tags"].clone())?.unwrap_or_default(),
     })
 }
 
@@ -79,4 +164,72 @@ fn as_naive_date_time(value: Value) -> Result<Option<DateTime<Utc>>, TogglApiErr
     } else {
         Err(ValueError("Could not parse value!".to_string()))
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use chrono::{Duration, NaiveDate, TimeZone, Utc};
+    use crate::config::Toggl;
+    use crate::toggl::{get_time_entries, get_time_entries_since};
+
+    /// serves a single http response on a random local port and returns its url
+    fn mock_server(body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            let _ = stream.read(&mut request).unwrap();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+        url
+    }
+
+    #[test]
+    fn test_get_time_entries_since() {
+        let url = mock_server(r#"[
+            {"id": 1, "description": "coding", "start": "2023-07-03T08:00:00Z", "stop": "2023-07-03T12:00:00Z", "project_id": null, "workspace_id": 42, "billable": true, "tags": ["rust"], "server_deleted_at": null},
+            {"id": 2, "description": null, "start": "2023-07-03T13:00:00Z", "stop": null, "project_id": null, "workspace_id": 42, "server_deleted_at": "2023-07-04T08:00:00Z"}
+        ]"#);
+        let config = Toggl {
+            username: "token".to_string(),
+            password: "api_token".to_string(),
+            api_url: Some(url),
+        };
+
+        let since = Utc.with_ymd_and_hms(2023, 7, 1, 0, 0, 0).unwrap();
+        let changes = get_time_entries_since(&config, &since).unwrap();
+        assert_eq!(1, changes.updated.len());
+        assert_eq!(Some("coding".to_string()), changes.updated[0].description);
+        assert_eq!(vec!["rust".to_string()], changes.updated[0].tags);
+        assert!(changes.updated[0].billable);
+        assert_eq!(vec![2], changes.deleted);
+    }
+
+    #[test]
+    fn test_get_time_entries_keeps_unreadable_ids() {
+        let url = mock_server(r#"[
+            {"id": 1, "description": "coding", "start": "2023-07-03T08:00:00Z", "stop": "2023-07-03T12:00:00Z", "project_id": null, "workspace_id": 42},
+            {"id": 3, "description": "broken", "start": "yesterday", "stop": null, "project_id": null, "workspace_id": 42}
+        ]"#);
+        let config = Toggl {
+            username: "token".to_string(),
+            password: "api_token".to_string(),
+            api_url: Some(url),
+        };
+
+        let start = NaiveDate::from_ymd_opt(2023, 7, 1).unwrap();
+        let window = get_time_entries(&config, &start, &(start + Duration::days(7))).unwrap();
+        assert_eq!(1, window.time_entries.len());
+        assert_eq!(vec![1, 3], window.ids);
+        assert_eq!(vec![3], window.unreadable);
+    }
+}